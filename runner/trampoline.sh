@@ -0,0 +1,230 @@
+# Builds and runs a program inside the sandbox. This replaces the runner
+# image's own trampoline so that we control each phase of a run.
+#
+# It is passed to the container as a single argument and invoked as
+#   sh -c "$script" trampoline key=value...
+# with the program itself base64 encoded in code=. Nothing in here is ever
+# interpreted by a shell on the host.
+#
+# The outcome of a phase is reported to the bot on the last line of stderr,
+# prefixed with "ferris-bot:".
+
+set -u
+
+compile_timeout=10
+run_timeout=5
+edition=2021
+mode=debug
+emit=asm-intel
+profile=debug
+crate_type=bin
+error_code=
+code=
+
+for arg in "$@"; do
+    case "$arg" in
+        compile-timeout=*) compile_timeout="${arg#*=}" ;;
+        run-timeout=*) run_timeout="${arg#*=}" ;;
+        edition=*) edition="${arg#*=}" ;;
+        mode=*) mode="${arg#*=}" ;;
+        emit=*) emit="${arg#*=}" ;;
+        profile=*) profile="${arg#*=}" ;;
+        crate-type=*) crate_type="${arg#*=}" ;;
+        error-code=*) error_code="${arg#*=}" ;;
+        code=*) code="${arg#*=}" ;;
+    esac
+done
+
+report() {
+    echo "ferris-bot: $*" >&2
+}
+
+# Reports how the program itself exited, so its exit code can't be taken for
+# the container engine's. The shell can't tell exiting with 128 + N from being
+# killed by signal N, so only the signals programs actually die of in here
+# count as such
+report_exit() {
+    case "$1" in
+        132|134|135|136|137|139) report signal $(($1 - 128)) ;;
+        *) report exit "$1" ;;
+    esac
+}
+
+# Milliseconds since the epoch
+now() {
+    echo $(($(date +%s%N) / 1000000))
+}
+
+# Turns seconds as the bot passes them, with three decimals, into milliseconds
+millis() {
+    ms=$(printf '%s' "$1" | tr -d . | sed 's/^0*//')
+    echo "${ms:-0}"
+}
+
+# Runs a command for at most $1 seconds, leaving its exit code in $status and
+# whether it was stopped for taking too long in $timed_out. timeout exits with
+# 124 when the time is up, or with 137 when -k had to kill a command that
+# ignored SIGTERM, but the command can exit with those by itself too, so the
+# clock decides
+limited() {
+    limit=$1
+    shift
+    started=$(now)
+    timeout -k 1 "$limit" "$@"
+    status=$?
+    timed_out=false
+    case "$status" in
+        124|137) [ $(($(now) - started)) -ge "$(millis "$limit")" ] && timed_out=true ;;
+    esac
+}
+
+# How many times the OOM killer struck in our cgroup, 0 when we can't see it
+oom_kills() {
+    kills=$(sed -n 's/^oom_kill //p' /sys/fs/cgroup/memory.events 2>/dev/null)
+    echo "${kills:-0}"
+}
+
+# Reports how the program in the run phase ended, telling running out of time
+# or memory apart from the program's own exit
+report_run() {
+    if $timed_out; then
+        report timeout run
+        exit 124
+    elif [ "$status" -eq 137 ] && [ "$(oom_kills)" -gt "$oom_before" ]; then
+        report out-of-memory
+        exit "$status"
+    fi
+}
+
+work=$(mktemp -d) || exit 125
+cd "$work" || exit 125
+printf '%s' "$code" | base64 -d > main.rs || exit 125
+
+# The explanation comes back on stdout, rustc fails for codes it doesn't know
+if [ "$mode" = explain ]; then
+    timeout -k 1 "$compile_timeout" rustc --explain "$error_code"
+    exit
+fi
+
+# The formatted code comes back on stdout, parse errors on stderr
+if [ "$mode" = fmt ]; then
+    limited "$compile_timeout" rustfmt --edition "$edition" < main.rs
+    if $timed_out; then
+        report timeout compile
+        exit 124
+    elif [ "$status" -ne 0 ]; then
+        report compile-error
+    fi
+    exit "$status"
+fi
+
+# What the compiler made of the code comes back on stdout
+if [ "$mode" = emit ]; then
+    # A single codegen unit makes for a single output file, otherwise rustc
+    # may split it up and ignore -o
+    case "$profile" in
+        release) flags="-O -C codegen-units=1" ;;
+        *) flags="-C codegen-units=1" ;;
+    esac
+    # Libraries leave out functions nothing calls, and small ones that can be
+    # inlined into other crates, unless dead code is kept
+    case "$crate_type" in
+        lib) flags="$flags --crate-type=lib -C link-dead-code" ;;
+        *) flags="$flags --crate-type=bin" ;;
+    esac
+    case "$emit" in
+        asm-intel) flags="$flags --emit asm -C llvm-args=-x86-asm-syntax=intel" ;;
+        asm-att) flags="$flags --emit asm" ;;
+        llvm-ir) flags="$flags --emit llvm-ir" ;;
+        mir) flags="$flags --emit mir" ;;
+        hir) flags="$flags -Zunpretty=hir" ;;
+        *) exit 125 ;;
+    esac
+
+    limited "$compile_timeout" rustc --edition "$edition" $flags -o emitted main.rs
+    if $timed_out; then
+        report timeout compile
+        exit 124
+    elif [ "$status" -ne 0 ]; then
+        report compile-error
+        exit "$status"
+    fi
+    cat emitted
+    exit 0
+fi
+
+# Miri builds and interprets the program in one go, so the whole thing gets
+# the run budget
+if [ "$mode" = miri ]; then
+    mkdir src && mv main.rs src/main.rs || exit 125
+    printf '[package]\nname = "playground"\nversion = "0.0.0"\nedition = "%s"\n' \
+        "$edition" > Cargo.toml || exit 125
+
+    # Whether the program failed to compile or did something undefined is
+    # told by the bot from Miri's output
+    oom_before=$(oom_kills)
+    limited "$run_timeout" cargo miri run --quiet --offline
+    report_run
+    report_exit "$status"
+    exit "$status"
+fi
+
+# The expanded code comes back on stdout
+if [ "$mode" = expand ]; then
+    limited "$compile_timeout" rustc --edition "$edition" -Zunpretty=expanded main.rs
+    if $timed_out; then
+        report timeout compile
+        exit 124
+    elif [ "$status" -ne 0 ]; then
+        report compile-error
+    fi
+    exit "$status"
+fi
+
+# Lints come back as cargo's JSON messages on stdout, the bot renders them
+if [ "$mode" = clippy ]; then
+    # clippy only runs on a cargo project
+    mkdir src && mv main.rs src/main.rs || exit 125
+    printf '[package]\nname = "playground"\nversion = "0.0.0"\nedition = "%s"\n' \
+        "$edition" > Cargo.toml || exit 125
+
+    limited "$compile_timeout" cargo clippy --quiet --offline --message-format=json
+    if $timed_out; then
+        report timeout compile
+        exit 124
+    elif [ "$status" -ne 0 ]; then
+        report compile-error
+    fi
+    exit "$status"
+fi
+
+case "$mode" in
+    release) flags="-O" ;;
+    test) flags="--test" ;;
+    *) flags= ;;
+esac
+
+# Diagnostics come out as JSON so the bot can lay them out itself
+limited "$compile_timeout" rustc --edition "$edition" --error-format=json $flags -o main main.rs
+if $timed_out; then
+    report timeout compile
+    exit 124
+elif [ "$status" -ne 0 ]; then
+    report compile-error
+    exit "$status"
+fi
+
+if [ "$mode" = build ]; then
+    exit 0
+fi
+
+oom_before=$(oom_kills)
+limited "$run_timeout" ./main
+report_run
+if [ "$mode" = test ] && [ "$status" -ne 0 ]; then
+    report tests-failed
+    exit "$status"
+fi
+
+report_exit "$status"
+exit "$status"
@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+
+use crate::commands::run::{
+    attached_output, check_allowed, execute, format_list, format_output, RunModal, OUTPUT_LIMIT,
+};
+use crate::model::diagnostic::{Diagnostic, Severity};
+use crate::model::options::{Edition, RunOptions};
+use crate::model::outcome::{ExecutionOutcome, ExecutionStatus};
+use crate::model::trampoline::Task;
+use crate::Error;
+use serenity::prelude::Mentionable;
+use serenity::utils::Colour;
+
+/// Name of the embed field listing the lints of a severity
+fn heading(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "Errors",
+        Severity::Warning => "Warnings",
+        Severity::Note => "Notes",
+    }
+}
+
+/// One line describing a lint, eg. `clippy::needless_return` at 2:5 — unneeded `return` statement
+fn describe(diagnostic: &Diagnostic) -> String {
+    let name = match &diagnostic.code {
+        Some(code) => format!("`{}`", code.code),
+        None => String::from("`rustc`"),
+    };
+    match diagnostic.primary_span() {
+        Some(span) => format!(
+            "{} {} at {}:{} — {}",
+            diagnostic.severity().emoji(),
+            name,
+            span.line_start,
+            span.column_start,
+            diagnostic.message
+        ),
+        None => format!(
+            "{} {} — {}",
+            diagnostic.severity().emoji(),
+            name,
+            diagnostic.message
+        ),
+    }
+}
+
+async fn reply(
+    ctx: poise::ApplicationContext<'_, crate::Data, crate::Error>,
+    code: String,
+    options: RunOptions,
+    outcome: ExecutionOutcome,
+) -> Result<(), Error> {
+    let interaction = ctx.interaction.unwrap();
+
+    let diagnostics = Diagnostic::from_cargo_output(&outcome.stdout);
+    let mut grouped: BTreeMap<Severity, Vec<String>> = BTreeMap::new();
+    for diagnostic in &diagnostics {
+        grouped
+            .entry(diagnostic.severity())
+            .or_default()
+            .push(describe(diagnostic));
+    }
+
+    let mut attachments = Vec::new();
+    let mut fields = vec![(
+        "Code",
+        attached_output(code, Some("rs"), "code.rs", OUTPUT_LIMIT, &mut attachments),
+        true,
+    )];
+    for (severity, lines) in &grouped {
+        fields.push((heading(*severity), format_list(lines, OUTPUT_LIMIT), false));
+    }
+
+    // Lints and compile errors are what we came for, anything else means
+    // clippy itself didn't get to finish
+    let linted = matches!(
+        outcome.status,
+        ExecutionStatus::Success | ExecutionStatus::CompileError
+    );
+    let (title, colour) = if !linted {
+        if !outcome.stderr.is_empty() {
+            fields.push((
+                "Error",
+                attached_output(
+                    outcome.stderr,
+                    None,
+                    "stderr.txt",
+                    OUTPUT_LIMIT,
+                    &mut attachments,
+                ),
+                false,
+            ));
+        }
+        if let ExecutionStatus::SandboxFailure { reason } = &outcome.status {
+            fields.push(("Reason", format_output(reason.clone(), None), false));
+        }
+        (
+            format!("{} {}", outcome.status.emoji(), outcome.status),
+            outcome.status.colour(),
+        )
+    } else {
+        match grouped.keys().next() {
+            None => (
+                String::from("✅ Clippy has no complaints"),
+                Colour::DARK_GREEN,
+            ),
+            Some(worst) => (
+                format!("{} Clippy found {} lints", worst.emoji(), diagnostics.len()),
+                worst.colour(),
+            ),
+        }
+    };
+
+    interaction
+        .channel_id
+        .send_message(&ctx.discord.http, |m| {
+            m.content(format!("{} linted", interaction.user.mention()));
+            m.add_files(attachments);
+            m.embed(|e| {
+                e.title(title);
+                e.colour(colour);
+                e.fields(fields);
+                e.footer(|f| {
+                    f.text(format!(
+                        "{} edition · Linted in {:.2}s",
+                        options.edition,
+                        outcome.duration.as_secs_f64()
+                    ))
+                });
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+/// Lints your code with clippy
+#[poise::command(slash_command)]
+pub async fn clippy(
+    ctx: poise::ApplicationContext<'_, crate::Data, crate::Error>,
+    #[description = "Rust edition to lint your code with, 2021 by default"] edition: Option<
+        Edition,
+    >,
+) -> Result<(), Error> {
+    use poise::Modal as _;
+
+    let options = RunOptions {
+        edition: edition.unwrap_or_default(),
+        ..Default::default()
+    };
+    if !check_allowed(ctx.into(), &options).await? {
+        return Ok(());
+    }
+
+    let code = RunModal::execute(ctx).await?.code_to_run;
+    let outcome = execute(ctx.into(), &code, &options, Task::Clippy).await?;
+
+    reply(ctx, code, options, outcome).await
+}
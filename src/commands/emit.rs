@@ -0,0 +1,124 @@
+use crate::commands::run::{
+    attached_output, check_allowed, execute, format_output, RunModal, OUTPUT_LIMIT,
+};
+use crate::model::options::{Edition, Emit, Profile, RunOptions};
+use crate::model::outcome::{ExecutionOutcome, ExecutionStatus};
+use crate::model::toolchain::Toolchain;
+use crate::model::trampoline::Task;
+use crate::Error;
+use serenity::prelude::Mentionable;
+
+async fn reply(
+    ctx: poise::ApplicationContext<'_, crate::Data, crate::Error>,
+    code: String,
+    options: RunOptions,
+    emit: Emit,
+    profile: Profile,
+    outcome: ExecutionOutcome,
+) -> Result<(), Error> {
+    let interaction = ctx.interaction.unwrap();
+
+    let mut attachments = Vec::new();
+    let mut fields = vec![(
+        "Code",
+        attached_output(code, Some("rs"), "code.rs", OUTPUT_LIMIT, &mut attachments),
+        true,
+    )];
+
+    if outcome.status == ExecutionStatus::Success {
+        // Assembly gets long fast, rather than cutting it short hand it over as a file
+        fields.push((
+            "Output",
+            attached_output(
+                outcome.stdout,
+                Some(emit.syntax()),
+                emit.file_name(),
+                OUTPUT_LIMIT,
+                &mut attachments,
+            ),
+            false,
+        ));
+    } else if !outcome.stderr.is_empty() {
+        fields.push((
+            "Error",
+            attached_output(
+                outcome.stderr,
+                None,
+                "stderr.txt",
+                OUTPUT_LIMIT,
+                &mut attachments,
+            ),
+            false,
+        ));
+    }
+
+    if let ExecutionStatus::SandboxFailure { reason } = &outcome.status {
+        fields.push(("Reason", format_output(reason.clone(), None), false));
+    }
+
+    interaction
+        .channel_id
+        .send_message(&ctx.discord.http, |m| {
+            m.content(format!("{} compiled", interaction.user.mention()));
+            m.add_files(attachments);
+            m.embed(|e| {
+                e.title(format!("{} {}", outcome.status.emoji(), outcome.status));
+                e.colour(outcome.status.colour());
+                e.fields(fields);
+                e.footer(|f| {
+                    f.text(format!(
+                        "{} · {} · {} · Compiled in {:.2}s",
+                        options,
+                        profile,
+                        emit,
+                        outcome.duration.as_secs_f64()
+                    ))
+                });
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+/// Shows the assembly, LLVM IR, MIR or HIR of your code
+#[poise::command(slash_command)]
+pub async fn emit(
+    ctx: poise::ApplicationContext<'_, crate::Data, crate::Error>,
+    #[description = "What to show, intel assembly by default"] emit: Option<Emit>,
+    #[description = "Compile with or without optimizations, debug by default"] profile: Option<
+        Profile,
+    >,
+    #[description = "Toolchain to compile your code with, stable by default"] channel: Option<
+        Toolchain,
+    >,
+    #[description = "Rust edition to compile your code with, 2021 by default"] edition: Option<
+        Edition,
+    >,
+) -> Result<(), Error> {
+    use poise::Modal as _;
+
+    let emit = emit.unwrap_or_default();
+    let profile = profile.unwrap_or_default();
+
+    // HIR is only available through unstable flags
+    let task = Task::Emit { emit, profile };
+    let toolchain = if task.needs_nightly() {
+        Toolchain::Nightly
+    } else {
+        channel.unwrap_or_default()
+    };
+    let options = RunOptions {
+        toolchain,
+        edition: edition.unwrap_or_default(),
+        ..Default::default()
+    };
+    if !check_allowed(ctx.into(), &options).await? {
+        return Ok(());
+    }
+
+    let code = RunModal::execute(ctx).await?.code_to_run;
+    let outcome = execute(ctx.into(), &code, &options, task).await?;
+
+    reply(ctx, code, options, emit, profile, outcome).await
+}
@@ -0,0 +1,33 @@
+use crate::commands::run::{check_allowed, execute, reply, RunModal};
+use crate::model::options::{Edition, RunOptions};
+use crate::model::toolchain::Toolchain;
+use crate::model::trampoline::Task;
+use crate::Error;
+
+/// Shows your code with all its macros expanded
+#[poise::command(slash_command)]
+pub async fn expand(
+    ctx: poise::ApplicationContext<'_, crate::Data, crate::Error>,
+    #[description = "Rust edition to expand your code with, 2021 by default"] edition: Option<
+        Edition,
+    >,
+) -> Result<(), Error> {
+    use poise::Modal as _;
+
+    // Expanding is only available through unstable flags
+    let options = RunOptions {
+        toolchain: Toolchain::Nightly,
+        edition: edition.unwrap_or_default(),
+        ..Default::default()
+    };
+    if !check_allowed(ctx.into(), &options).await? {
+        return Ok(());
+    }
+
+    let code = RunModal::execute(ctx).await?.code_to_run;
+    let outcome = execute(ctx.into(), &code, &options, Task::Expand).await?;
+
+    reply(ctx.into(), None, code, options, Task::Expand, outcome).await?;
+
+    Ok(())
+}
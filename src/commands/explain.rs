@@ -0,0 +1,187 @@
+use crate::commands::run::{limited_message, shorten};
+use crate::model::explain::ErrorCode;
+use crate::model::limits::Limited;
+use crate::model::options::RunOptions;
+use crate::model::outcome::ExecutionStatus;
+use crate::model::queue::STARTED;
+use crate::model::runnable::*;
+use crate::model::trampoline::Task;
+use crate::{Data, Error};
+use poise::serenity_prelude as serenity;
+use serenity::builder::CreateEmbed;
+use serenity::model::interactions::message_component::MessageComponentInteraction;
+use serenity::model::interactions::InteractionResponseType;
+
+/// Longest explanation we show, embed descriptions are capped at 4096 chars
+const EXPLANATION_LIMIT: usize = 4000;
+
+/// Checks `user` may ask rustc about `code`, which counts as a run unless
+/// its explanation is cached already
+fn check_allowed(
+    data: &Data,
+    code: ErrorCode,
+    user: serenity::UserId,
+    channel: serenity::ChannelId,
+    guild: Option<serenity::GuildId>,
+) -> Result<(), Limited> {
+    if data.explanations.get(code).is_some() {
+        return Ok(());
+    }
+    data.limiter.check(user, channel, guild)
+}
+
+/// Gets rustc's explanation of `code`, asking rustc only the first time and
+/// charging `user` for it
+///
+/// Returns None for codes rustc doesn't know
+pub async fn explanation(
+    data: &Data,
+    user: serenity::UserId,
+    code: ErrorCode,
+) -> Result<Option<String>, Error> {
+    if let Some(explanation) = data.explanations.get(code) {
+        return Ok(explanation);
+    }
+
+    // Explaining is quick, so there's no point in telling anyone about the queue
+    let mut ticket = data.queue.join(user);
+    let mut position = ticket.position();
+    while position != STARTED {
+        position = ticket.changed().await;
+    }
+
+    let outcome = String::new()
+        .run(&data.pool, &RunOptions::default(), Task::Explain(code))
+        .await;
+    drop(ticket);
+
+    let cpu_seconds = outcome.duration.as_secs_f64() * data.pool.settings().cpus();
+    data.limiter.charge(user, cpu_seconds);
+
+    let explanation = match outcome.status {
+        ExecutionStatus::Success => Some(outcome.stdout),
+        ExecutionStatus::RuntimeError { .. } => None,
+        // Anything else is on our end, try again next time
+        status => return Err(status.to_string().into()),
+    };
+
+    data.explanations.insert(code, explanation.clone());
+    Ok(explanation)
+}
+
+/// Fills in an embed with the explanation of `code`
+fn explanation_embed(
+    e: &mut CreateEmbed,
+    code: ErrorCode,
+    explanation: Option<String>,
+) -> &mut CreateEmbed {
+    e.title(code);
+    match explanation {
+        Some(explanation) => {
+            e.url(code.url());
+            if explanation.len() > EXPLANATION_LIMIT {
+                e.description(format!(
+                    "{}\n\n[Read the rest online]({})",
+                    shorten(&explanation, EXPLANATION_LIMIT),
+                    code.url()
+                ))
+            } else {
+                e.description(explanation)
+            }
+        }
+        None => e.description(format!("rustc doesn't know about {}", code)),
+    }
+}
+
+/// Answers clicks on the explain buttons under /run replies, privately
+pub async fn handle_button(
+    ctx: &serenity::Context,
+    data: &Data,
+    component: &MessageComponentInteraction,
+) -> Result<(), Error> {
+    let code = match ErrorCode::from_custom_id(&component.data.custom_id) {
+        Some(code) => code,
+        // Some other command's button
+        None => return Ok(()),
+    };
+
+    // Asking rustc can take longer than discord waits for an answer
+    component
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                .interaction_response_data(|d| d.ephemeral(true))
+        })
+        .await?;
+
+    let user = component.user.id;
+    if let Err(limited) = check_allowed(data, code, user, component.channel_id, component.guild_id)
+    {
+        component
+            .edit_original_interaction_response(&ctx.http, |m| m.content(limited_message(&limited)))
+            .await?;
+        return Ok(());
+    }
+
+    match explanation(data, user, code).await {
+        Ok(explanation) => {
+            component
+                .edit_original_interaction_response(&ctx.http, |m| {
+                    m.embed(|e| explanation_embed(e, code, explanation))
+                })
+                .await?;
+        }
+        Err(e) => {
+            println!("Error explaining {}: {:?}", code, e);
+            component
+                .edit_original_interaction_response(&ctx.http, |m| {
+                    m.content(format!("Could not get the explanation of {}", code))
+                })
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Explains a compiler error code, like rustc --explain does
+#[poise::command(slash_command)]
+pub async fn explain(
+    ctx: poise::ApplicationContext<'_, crate::Data, crate::Error>,
+    #[description = "The error code to explain, eg. E0382"] code: String,
+) -> Result<(), Error> {
+    let code: ErrorCode = match code.parse() {
+        Ok(code) => code,
+        Err(e) => {
+            poise::send_application_reply(ctx, |m| m.content(e.to_string()).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let interaction = ctx.interaction.unwrap();
+    let user = interaction.user.id;
+    if let Err(limited) = check_allowed(
+        ctx.data,
+        code,
+        user,
+        interaction.channel_id,
+        interaction.guild_id,
+    ) {
+        poise::send_application_reply(ctx, |m| {
+            m.content(limited_message(&limited)).ephemeral(true)
+        })
+        .await?;
+        return Ok(());
+    }
+
+    // Asking rustc can take longer than discord waits for an answer
+    poise::Context::Application(ctx).defer().await?;
+
+    let explanation = explanation(ctx.data, user, code).await?;
+    poise::send_application_reply(ctx, |m| {
+        m.embed(|e| explanation_embed(e, code, explanation))
+    })
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use crate::commands::run::{
+    attached_output, check_allowed, execute, format_output, RunModal, DESCRIPTION_LIMIT,
+};
+use crate::model::codeblock::extract_code;
+use crate::model::options::{Edition, RunOptions};
+use crate::model::outcome::{ExecutionOutcome, ExecutionStatus};
+use crate::model::trampoline::Task;
+use crate::{Context, Error};
+use poise::serenity_prelude::Message;
+use serenity::prelude::Mentionable;
+use serenity::utils::Colour;
+
+async fn reply(
+    ctx: Context<'_>,
+    source: Option<&Message>,
+    options: RunOptions,
+    outcome: ExecutionOutcome,
+) -> Result<(), Error> {
+    let mut fields = Vec::new();
+    let mut attachments = Vec::new();
+    let (title, colour, description) = match &outcome.status {
+        // The code gets the whole description, and is attached when it's
+        // longer than that as half of it is of no use
+        ExecutionStatus::Success => (
+            String::from("✨ Formatted"),
+            Colour::DARK_GREEN,
+            attached_output(
+                outcome.stdout,
+                Some("rust"),
+                "formatted.rs",
+                DESCRIPTION_LIMIT,
+                &mut attachments,
+            ),
+        ),
+        // rustfmt only fails like this when it can't parse the code
+        ExecutionStatus::CompileError => (
+            String::from("❌ Your code doesn't parse"),
+            outcome.status.colour(),
+            attached_output(
+                outcome.stderr,
+                None,
+                "stderr.txt",
+                DESCRIPTION_LIMIT,
+                &mut attachments,
+            ),
+        ),
+        status => {
+            if let ExecutionStatus::SandboxFailure { reason } = status {
+                fields.push(("Reason", format_output(reason.clone(), None), false));
+            }
+            (
+                format!("{} {}", status.emoji(), status),
+                status.colour(),
+                attached_output(
+                    outcome.stderr,
+                    None,
+                    "stderr.txt",
+                    DESCRIPTION_LIMIT,
+                    &mut attachments,
+                ),
+            )
+        }
+    };
+
+    ctx.channel_id()
+        .send_message(&ctx.discord().http, |m| {
+            // Keep the formatted code next to the original one
+            if let Some(source) = source {
+                m.reference_message(source);
+            }
+            m.content(format!("{} formatted", ctx.author().mention()));
+            m.add_files(attachments);
+            m.embed(|e| {
+                e.title(title);
+                e.colour(colour);
+                e.description(description);
+                e.fields(fields);
+                e.footer(|f| f.text(format!("{} edition", options.edition)));
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+/// Formats your code with rustfmt
+///
+/// Paste your code when used as a slash command, or reply to a message with
+/// code in it with !fmt
+#[poise::command(slash_command, prefix_command)]
+pub async fn fmt(
+    ctx: Context<'_>,
+    #[description = "Rust edition to format your code for, 2021 by default"] edition: Option<
+        Edition,
+    >,
+) -> Result<(), Error> {
+    use poise::Modal as _;
+
+    let options = RunOptions {
+        edition: edition.unwrap_or_default(),
+        ..Default::default()
+    };
+
+    let (code, source) = match ctx {
+        poise::Context::Application(ctx) => {
+            if !check_allowed(ctx.into(), &options).await? {
+                return Ok(());
+            }
+            (RunModal::execute(ctx).await?.code_to_run, None)
+        }
+        poise::Context::Prefix(prefix) => {
+            let source = match &prefix.msg.referenced_message {
+                Some(source) => source.as_ref(),
+                None => {
+                    ctx.say("Reply to a message with some code to format it")
+                        .await?;
+                    return Ok(());
+                }
+            };
+            if !check_allowed(ctx, &options).await? {
+                return Ok(());
+            }
+            (extract_code(&source.content), Some(source))
+        }
+    };
+
+    let outcome = execute(ctx, &code, &options, Task::Fmt).await?;
+
+    reply(ctx, source, options, outcome).await
+}
@@ -1,2 +1,10 @@
+pub mod clippy;
+pub mod emit;
+pub mod expand;
+pub mod explain;
+pub mod fmt;
 pub mod quiz;
+pub mod rerun;
 pub mod run;
+pub mod run_message;
+pub mod run_prefix;
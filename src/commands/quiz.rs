@@ -90,7 +90,7 @@ pub async fn quiz(
 
     // Wait for a responses within a certain amount of time
     let mut cib = m
-        .await_component_interactions(&ctx.discord)
+        .await_component_interactions(ctx.discord)
         .timeout(Duration::from_secs(QUESTION_TIME))
         .build();
 
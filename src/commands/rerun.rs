@@ -0,0 +1,134 @@
+use crate::commands::run::{execute_for, limited_message, run_result};
+use crate::commands::run_prefix::parse_input;
+use crate::model::codeblock::rust_blocks;
+use crate::model::explain::ErrorCode;
+use crate::model::options::RunOptions;
+use crate::model::tracker::{CodeSource, TrackedRun};
+use crate::model::trampoline::Task;
+use crate::{Data, Error};
+use poise::serenity_prelude as serenity;
+use serenity::{ChannelId, Mentionable, MessageId, MessageUpdateEvent};
+
+/// Re-runs the code of an edited message we ran before, and puts the new
+/// outcome in place of the old one
+pub async fn handle_edit(
+    ctx: &serenity::Context,
+    data: &Data,
+    update: &MessageUpdateEvent,
+) -> Result<(), Error> {
+    // Edits without content are discord adding embeds for links and the like
+    let content = match &update.content {
+        Some(content) => content,
+        None => return Ok(()),
+    };
+    let run = match data.tracker.get(update.id) {
+        Some(run) => run,
+        None => return Ok(()),
+    };
+    // Quick edits run side by side, only the latest one gets to show its result
+    let edit = data.tracker.begin_edit(update.id);
+
+    let parsed = match &run.source {
+        CodeSource::Prefix { invocation } => {
+            match content.trim_start().strip_prefix(invocation.as_str()) {
+                Some(input) => parse_input(input.trim_start()),
+                // It's not a run anymore, leave the last result be
+                None => return Ok(()),
+            }
+        }
+        CodeSource::Block { index } => match rust_blocks(content).into_iter().nth(*index) {
+            Some(code) => Ok((RunOptions::default(), code)),
+            None => return Ok(()),
+        },
+    };
+
+    let channel = update.channel_id;
+    let notice = |text: String| async move {
+        if data.tracker.is_latest_edit(update.id, edit) {
+            channel
+                .edit_message(&ctx.http, run.result, |m| m.content(text))
+                .await?;
+        }
+        Ok::<(), Error>(())
+    };
+
+    let (options, code) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            notice(format!("{} {}", run.user.mention(), e)).await?;
+            return Ok(());
+        }
+    };
+    // Discord unfurling a link, or a typo fixed outside of the code block
+    if code == run.code && options == run.options {
+        return Ok(());
+    }
+    if let Some(reason) = options.unavailable() {
+        notice(reason).await?;
+        return Ok(());
+    }
+    if let Err(limited) = data.limiter.check(run.user, channel, update.guild_id) {
+        notice(format!(
+            "{} {}",
+            run.user.mention(),
+            limited_message(&limited)
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let task = Task::Run(options.mode);
+    let outcome = execute_for(ctx, data, run.user, channel, &code, &options, task).await?;
+    let result = run_result(code.clone(), options, task, outcome);
+    if !data.tracker.is_latest_edit(update.id, edit) {
+        return Ok(());
+    }
+
+    // The files of the last run don't belong to this one
+    let previous = channel.message(&ctx.http, run.result).await?;
+
+    channel
+        .edit_message(&ctx.http, run.result, |m| {
+            m.content(format!("{} ran", run.user.mention()));
+            m.set_embed(result.embed);
+            for attachment in &previous.attachments {
+                m.remove_existing_attachment(attachment.id);
+            }
+            for attachment in result.attachments {
+                m.attachment(attachment);
+            }
+            m.components(|c| {
+                if !result.error_codes.is_empty() {
+                    c.add_action_row(ErrorCode::action_row(&result.error_codes));
+                }
+                c
+            })
+        })
+        .await?;
+
+    data.tracker.track_edit(
+        update.id,
+        edit,
+        TrackedRun {
+            code,
+            options,
+            ..run
+        },
+    );
+
+    Ok(())
+}
+
+/// Deletes our result along with the message whose code we ran
+pub async fn handle_delete(
+    ctx: &serenity::Context,
+    data: &Data,
+    channel: ChannelId,
+    source: MessageId,
+) -> Result<(), Error> {
+    if let Some(run) = data.tracker.forget(source) {
+        channel.delete_message(&ctx.http, run.result).await?;
+    }
+
+    Ok(())
+}
@@ -1,86 +1,496 @@
+use crate::model::diagnostic::{Diagnostic, Severity};
+use crate::model::explain::ErrorCode;
+use crate::model::limits::Limited;
+use crate::model::options::{Edition, Mode, RunOptions};
+use crate::model::outcome::{ExecutionOutcome, ExecutionStatus};
+use crate::model::queue::{Ticket, STARTED};
 use crate::model::runnable::*;
-use crate::Error;
-use serenity::prelude::Mentionable;
+use crate::model::toolchain::Toolchain;
+use crate::model::trampoline::Task;
+use crate::{Context, Data, Error};
+use poise::serenity_prelude as serenity;
+use serenity::{AttachmentType, ChannelId, CreateEmbed, Mentionable, Message, UserId};
+use std::borrow::Cow;
+use std::time::UNIX_EPOCH;
 
-use std::io::ErrorKind;
+/// Longest output shown in an embed field, embed fields are capped at 1024 chars
+pub const OUTPUT_LIMIT: usize = 1000;
+
+/// Longest output shown in an embed description, which is capped at 4096 chars
+pub const DESCRIPTION_LIMIT: usize = 4000;
+
+/// Room left next to the preview of something that's attached in full, for
+/// saying where the rest is
+const ATTACHED_NOTE_ROOM: usize = 100;
+
+/// Embed field names are capped at 256 chars
+const FIELD_NAME_LIMIT: usize = 256;
+
+/// How many compile errors get a field of their own, the rest are only listed.
+/// Embeds are capped at 6000 chars in total, so these are kept compact
+const MAX_ERROR_FIELDS: usize = 4;
+
+/// Longest field for a single compile error
+const ERROR_FIELD_LIMIT: usize = 500;
+
+/// Longest line of code shown for a compile error, with its underline
+const SNIPPET_LIMIT: usize = 300;
+
+/// Longest list of warnings or of errors that didn't get a field
+const WARNINGS_LIMIT: usize = 500;
 
 /// Given some stdout or stderr data, format it so that it can be rendered by discord
-fn format_output(response: String, syntax_highlight: Option<&str>) -> String {
-    if response.len() < 1000 {
+pub fn format_output(response: String, syntax_highlight: Option<&str>) -> String {
+    if response.len() < OUTPUT_LIMIT {
         // Response falls within size constraints
         format!("```{}\n{}\n```", syntax_highlight.unwrap_or(""), response)
     } else {
         // For UX, truncate components to 1000 chars... should be long enough
-        let short_repsonse = &response[0..1000];
         format!(
             "```{}\n{}[TRUNCATED]```",
             syntax_highlight.unwrap_or(""),
-            short_repsonse
+            shorten(&response, OUTPUT_LIMIT)
         )
     }
 }
 
-async fn reply(
-    ctx: poise::ApplicationContext<'_, crate::Data, crate::Error>,
-    code: String,
-    stdout: Option<String>,
-    stderr: Option<String>,
-) -> Result<(), Error> {
-    let interaction = ctx.interaction.unwrap();
-    let channel = match interaction.channel_id.to_channel(&ctx.discord.http).await {
-        Ok(channel) => channel,
-        Err(why) => {
-            println!("Error getting channel: {:?}", why);
-            return Ok(());
+/// Puts `text` in a code block when it's shorter than `limit`, and otherwise
+/// shows the start of it while attaching all of it as `file_name`
+pub fn attached_output(
+    text: String,
+    syntax_highlight: Option<&str>,
+    file_name: &str,
+    limit: usize,
+    attachments: &mut Vec<AttachmentType<'static>>,
+) -> String {
+    let syntax = syntax_highlight.unwrap_or("");
+    if text.len() < limit {
+        return format!("```{}\n{}\n```", syntax, text);
+    }
+
+    let preview = format!(
+        "```{}\n{}\n```\nAll of it is in `{}`",
+        syntax,
+        shorten(&text, limit - ATTACHED_NOTE_ROOM),
+        file_name
+    );
+    attachments.push(AttachmentType::Bytes {
+        data: Cow::Owned(text.into_bytes()),
+        filename: file_name.to_string(),
+    });
+    preview
+}
+
+/// Puts one line per item in an embed field, leaving out what doesn't fit in
+/// `limit` chars
+pub fn format_list(lines: &[String], limit: usize) -> String {
+    let mut list = String::new();
+    for (shown, line) in lines.iter().enumerate() {
+        if list.len() + line.len() + 1 > limit {
+            list.push_str(&format!("...and {} more", lines.len() - shown));
+            break;
         }
+        list.push_str(line);
+        list.push('\n');
+    }
+    list
+}
+
+/// Cuts `text` down to at most `limit` bytes, without splitting a character
+pub fn shorten(text: &str, limit: usize) -> String {
+    if text.len() <= limit {
+        return text.to_string();
+    }
+
+    let mut end = limit.saturating_sub('…'.len_utf8());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…", &text[..end])
+}
+
+/// An embed field for a compile error, with the line it's about and rustc's
+/// suggestions for fixing it
+fn error_field(diagnostic: &Diagnostic) -> (String, String, bool) {
+    let name = shorten(
+        &format!(
+            "{} {}",
+            diagnostic.severity().emoji(),
+            diagnostic.headline()
+        ),
+        FIELD_NAME_LIMIT,
+    );
+
+    let mut value = match diagnostic.snippet() {
+        Some(snippet) => format!("```rs\n{}\n```", shorten(&snippet, SNIPPET_LIMIT)),
+        None => String::new(),
     };
-    let member = interaction.member.clone().unwrap();
+    for suggestion in diagnostic.suggestions() {
+        let line = format!("💡 {}\n", suggestion);
+        if value.len() + line.len() > ERROR_FIELD_LIMIT {
+            break;
+        }
+        value.push_str(&line);
+    }
+
+    // Embed fields can't be empty
+    if value.is_empty() {
+        value.push('\u{200b}');
+    }
+
+    (name, value, false)
+}
+
+/// What we reply with after running some code
+pub struct RunResult {
+    pub embed: CreateEmbed,
+    /// Error codes worth offering explanations of
+    pub error_codes: Vec<ErrorCode>,
+    /// Whatever was too long for the embed, in full
+    pub attachments: Vec<AttachmentType<'static>>,
+}
+
+/// The reply showing the outcome of running `task` on `code`
+pub fn run_result(
+    code: String,
+    options: RunOptions,
+    task: Task,
+    outcome: ExecutionOutcome,
+) -> RunResult {
+    let mut attachments = Vec::new();
 
     // TODO: probably a nicer way to do this
-    let mut fields = vec![("Code", format_output(code, Some("rs")), true)];
+    let mut fields = vec![(
+        String::from("Code"),
+        attached_output(code, Some("rs"), "code.rs", OUTPUT_LIMIT, &mut attachments),
+        true,
+    )];
+
+    // In test mode, list how every test went
+    let tests = outcome.test_results();
+    if task == Task::Run(Mode::Test) && !tests.is_empty() {
+        let lines: Vec<String> = tests
+            .iter()
+            .map(|test| format!("{} `{}`", test.status.emoji(), test.name))
+            .collect();
+        fields.push((
+            String::from("Tests"),
+            format_list(&lines, OUTPUT_LIMIT),
+            false,
+        ));
+    }
+
+    // Show what Miri caught on its own, rather than buried in the rest of stderr
+    let ub_report = match outcome.status {
+        ExecutionStatus::UndefinedBehaviour => outcome.ub_report(),
+        _ => None,
+    };
+    let (stderr, ub_report) = match ub_report {
+        Some((stderr, report)) => (stderr, Some(report)),
+        None => (outcome.stderr.clone(), None),
+    };
+
+    // rustc's diagnostics get a field per error, while warnings are tucked
+    // away behind a spoiler as they're rarely why the code didn't work
+    let (diagnostics, stderr) = Diagnostic::from_json_lines(&stderr);
+    let (errors, warnings): (Vec<&Diagnostic>, Vec<&Diagnostic>) = diagnostics
+        .iter()
+        .partition(|diagnostic| diagnostic.severity() == Severity::Error);
 
-    // If stdout is present, add it to the fields
-    if let Some(stdout) = stdout {
-        // Ensure that the stdout is not empty
-        if !stdout.is_empty() {
-            fields.push(("Output", format_output(stdout, None), false));
+    for error in errors.iter().take(MAX_ERROR_FIELDS) {
+        fields.push(error_field(error));
+    }
+    if errors.len() > MAX_ERROR_FIELDS {
+        let lines: Vec<String> = errors[MAX_ERROR_FIELDS..]
+            .iter()
+            .map(|error| error.headline())
+            .collect();
+        fields.push((
+            format!("...and {} more errors", lines.len()),
+            format_list(&lines, WARNINGS_LIMIT),
+            false,
+        ));
+    }
+
+    if !warnings.is_empty() {
+        let lines: Vec<String> = warnings
+            .iter()
+            .map(|warning| match warning.primary_span() {
+                Some(span) => format!(
+                    "`{}:{}` {}",
+                    span.line_start,
+                    span.column_start,
+                    warning.headline()
+                ),
+                None => warning.headline(),
+            })
+            .collect();
+        fields.push((
+            format!("{} {} warnings", Severity::Warning.emoji(), warnings.len()),
+            format!("||{}||", format_list(&lines, WARNINGS_LIMIT)),
+            false,
+        ));
+    }
+
+    // Ensure that the stdout is not empty
+    if !outcome.stdout.is_empty() {
+        // Expanded code is still code
+        let (syntax, file_name) = match task {
+            Task::Expand => (Some("rs"), "expanded.rs"),
+            _ => (None, "output.txt"),
+        };
+        fields.push((
+            String::from("Output"),
+            attached_output(
+                outcome.stdout,
+                syntax,
+                file_name,
+                OUTPUT_LIMIT,
+                &mut attachments,
+            ),
+            false,
+        ));
+    }
+
+    if let Some(report) = ub_report {
+        fields.push((
+            String::from("Undefined behaviour"),
+            attached_output(
+                report,
+                None,
+                "undefined-behaviour.txt",
+                OUTPUT_LIMIT,
+                &mut attachments,
+            ),
+            false,
+        ));
+    }
+
+    // Offer to explain the errors rustc gave codes to
+    let mut error_codes: Vec<ErrorCode> = diagnostics
+        .iter()
+        .filter_map(|diagnostic| diagnostic.code.as_ref()?.code.parse().ok())
+        .collect();
+    for code in ErrorCode::find_all(&stderr) {
+        if !error_codes.contains(&code) {
+            error_codes.push(code);
         }
     }
 
-    // If stderr is present, add it to the fields
-    if let Some(stderr) = stderr {
-        // Ensure stderr is not empty
-        if !stderr.is_empty() {
-            fields.push(("Error", format_output(stderr, None), false));
+    // Ensure stderr is not empty
+    if !stderr.is_empty() {
+        fields.push((
+            String::from("Error"),
+            attached_output(stderr, None, "stderr.txt", OUTPUT_LIMIT, &mut attachments),
+            false,
+        ));
+    }
+
+    // Say what went wrong on our end, the user can't do anything about it but
+    // it helps when they report it
+    if let ExecutionStatus::SandboxFailure { reason } = &outcome.status {
+        fields.push((
+            String::from("Reason"),
+            format_output(reason.clone(), None),
+            false,
+        ));
+    }
+
+    let mut embed = CreateEmbed::default();
+    embed.title(format!("{} {}", outcome.status.emoji(), outcome.status));
+    embed.colour(outcome.status.colour());
+    embed.fields(fields);
+    embed.footer(|f| {
+        let mut footer = format!(
+            "{} · {} · Ran for {:.2}s",
+            options,
+            task,
+            outcome.duration.as_secs_f64()
+        );
+        if outcome.truncated {
+            footer.push_str(" · Output cut short");
         }
+        f.text(footer)
+    });
+
+    RunResult {
+        embed,
+        error_codes,
+        attachments,
     }
+}
 
-    channel
-        .id()
-        .send_message(&ctx.discord.http, |m| {
-            m.content(format!("{} ran", member.mention()));
-            m.embed(|e| {
-                e.fields(fields);
-                e
-            })
+/// Shows the outcome of running `task` on `code` the way /run does, as a reply
+/// to `source` when the code came from a message
+pub async fn reply(
+    ctx: Context<'_>,
+    source: Option<&Message>,
+    code: String,
+    options: RunOptions,
+    task: Task,
+    outcome: ExecutionOutcome,
+) -> Result<Message, Error> {
+    let result = run_result(code, options, task, outcome);
+
+    let message = ctx
+        .channel_id()
+        .send_message(&ctx.discord().http, |m| {
+            if let Some(source) = source {
+                m.reference_message(source);
+            }
+            m.content(format!("{} ran", ctx.author().mention()));
+            if !result.error_codes.is_empty() {
+                m.components(|c| c.add_action_row(ErrorCode::action_row(&result.error_codes)));
+            }
+            m.add_files(result.attachments);
+            m.set_embed(result.embed)
         })
         .await?;
+    Ok(message)
+}
+
+/// Waits until it's the job's turn in the queue, keeping the user posted on
+/// their position in a message that goes away once the job starts
+async fn wait_for_turn(
+    discord: &serenity::Context,
+    channel: ChannelId,
+    user: UserId,
+    ticket: &mut Ticket,
+) -> Result<(), Error> {
+    if ticket.position() == STARTED {
+        return Ok(());
+    }
+
+    let queued = |position: usize| {
+        format!(
+            "{} your code is queued, position {}",
+            user.mention(),
+            position
+        )
+    };
+
+    let mut message = channel
+        .send_message(&discord.http, |m| m.content(queued(ticket.position())))
+        .await?;
+
+    loop {
+        let position = ticket.changed().await;
+        if position == STARTED {
+            break;
+        }
+
+        message
+            .edit(discord, |m| m.content(queued(position)))
+            .await?;
+    }
+
+    message.delete(discord).await?;
     Ok(())
 }
 
 #[derive(Debug, poise::Modal)]
-#[allow(dead_code)] // fields only used for Debug print
-struct RunModal {
+pub struct RunModal {
     #[name = "Code you want to run"]
     #[placeholder = "fn main() {\n    println!(\"Hello, world!\");\n}"]
     #[paragraph]
-    code_to_run: String,
+    pub code_to_run: String,
+}
+
+/// Checks the toolchain (or Miri) is there and the user is within their
+/// limits, telling them privately if not
+///
+/// This has to happen before asking for code, as after the modal we can't
+/// answer privately anymore
+pub async fn check_allowed(ctx: Context<'_>, options: &RunOptions) -> Result<bool, Error> {
+    if let Some(reason) = options.unavailable() {
+        ctx.send(|m| m.content(reason).ephemeral(true)).await?;
+        return Ok(false);
+    }
+
+    if let Err(limited) =
+        ctx.data()
+            .limiter
+            .check(ctx.author().id, ctx.channel_id(), ctx.guild_id())
+    {
+        ctx.send(|m| m.content(limited_message(&limited)).ephemeral(true))
+            .await?;
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+/// Tells the user which limit they hit and when they can run code again
+pub fn limited_message(limited: &Limited) -> String {
+    let retry_at = limited
+        .retry_at()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    format!("{}, you can run code again <t:{}:R>", limited, retry_at)
+}
+
+/// Queues `task` for `code`, waits for its turn and runs it, charging the
+/// user for the CPU time it took
+pub async fn execute(
+    ctx: Context<'_>,
+    code: &String,
+    options: &RunOptions,
+    task: Task,
+) -> Result<ExecutionOutcome, Error> {
+    execute_for(
+        ctx.discord(),
+        ctx.data(),
+        ctx.author().id,
+        ctx.channel_id(),
+        code,
+        options,
+        task,
+    )
+    .await
+}
+
+/// `execute` for when there's no command invocation, like when re-running
+/// edited code
+pub async fn execute_for(
+    discord: &serenity::Context,
+    data: &Data,
+    user: UserId,
+    channel: ChannelId,
+    code: &String,
+    options: &RunOptions,
+    task: Task,
+) -> Result<ExecutionOutcome, Error> {
+    let mut ticket = data.queue.join(user);
+    wait_for_turn(discord, channel, user, &mut ticket).await?;
+
+    // This leverages the runnable trait we created for executing arbitrary strings of code
+    let outcome = code.run(&data.pool, options, task).await;
+
+    // Let the next job in before we talk to discord
+    drop(ticket);
+
+    let cpu_seconds = outcome.duration.as_secs_f64() * data.pool.settings().cpus();
+    data.limiter.charge(user, cpu_seconds);
+
+    println!(
+        "{} finished: {:?} after {:?}",
+        task, outcome.status, outcome.duration
+    );
+
+    Ok(outcome)
 }
 
 /// Runs whatever code you throw at it
 #[poise::command(slash_command)]
 pub async fn run(
     ctx: poise::ApplicationContext<'_, crate::Data, crate::Error>,
+    #[description = "Toolchain to run your code on, stable by default"] channel: Option<Toolchain>,
+    #[description = "Rust edition to compile your code with, 2021 by default"] edition: Option<
+        Edition,
+    >,
+    #[description = "What to do with your code, debug run by default"] mode: Option<Mode>,
 ) -> Result<(), Error> {
     use poise::Modal as _;
 
@@ -98,67 +508,30 @@ pub async fn run(
         }
     };
 
+    let mode = mode.unwrap_or_default();
+
+    // Miri only comes with nightly
+    let task = Task::Run(mode);
+    let toolchain = if task.needs_nightly() {
+        Toolchain::Nightly
+    } else {
+        channel.unwrap_or_default()
+    };
+    let options = RunOptions {
+        toolchain,
+        edition: edition.unwrap_or_default(),
+        mode,
+    };
+    if !check_allowed(ctx.into(), &options).await? {
+        return Ok(());
+    }
+
     let modal_data = RunModal::execute(ctx).await?;
     let raw_code = modal_data.code_to_run;
 
-    // This leverages the runnable trait we created for executing arbitrary strings of code
-    let run_result = raw_code.run().await;
-
-    match run_result {
-        Ok(output) => {
-            let mut stdout = String::new();
-            let mut stderr = String::new();
-
-            if !output.stdout.is_empty() {
-                stdout = match String::from_utf8(output.stdout) {
-                    Ok(v) => v,
-                    Err(e) => panic!("Invalid UTF-8 sequence: {}", e),
-                };
-
-                println!("Got stdout\n\"{}\"", stdout);
-            } else {
-                println!("No stdout");
-            }
-
-            if !output.stderr.is_empty() {
-                stderr = match String::from_utf8(output.stderr) {
-                    Ok(v) => v,
-                    Err(e) => panic!("Invalid UTF-8 sequence: {}", e),
-                };
-                println!("Got stderr \"{}\"", stderr);
-            } else {
-                println!("No stderr");
-            }
+    let outcome = execute(ctx.into(), &raw_code, &options, task).await?;
 
-            // TODO: better response classification
-            // in the original rustbot we used reactions to indicate successful or failed compilation
-            // or timeouts. With discord's command framework, it's a little more tricky.
-            // For now we just use a canned response for everything, in the future it would be nice to add more
-            // detailed responses for each type of response.
-            reply(ctx, raw_code, Some(stdout), Some(stderr)).await?;
-        }
-        Err(error) => {
-            // TODO: find out ways this can blow up
-            //println!("TIMEOUT on {}'s code", interaction.);
-            match error.kind() {
-                ErrorKind::TimedOut => {
-                    // Took too long to run, complain to user
-                    //msg.react(&ctx, CROSS_MARK_EMOJI).await?;
-                    //msg.react(&ctx, CLOCK_EMOJI).await?;
-                    reply(
-                        ctx,
-                        raw_code,
-                        None,
-                        Some("Your program took too long to run.".to_owned()),
-                    )
-                    .await?;
-                }
-                _ => {
-                    println!("Error: {:?}", error);
-                }
-            }
-        }
-    }
+    reply(ctx.into(), None, raw_code, options, task, outcome).await?;
 
     Ok(())
 }
@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+use crate::commands::run::{check_allowed, execute, reply};
+use crate::model::codeblock::rust_blocks;
+use crate::model::options::RunOptions;
+use crate::model::tracker::{CodeSource, TrackedRun};
+use crate::model::trampoline::Task;
+use crate::Error;
+use poise::serenity_prelude as serenity;
+use serenity::model::interactions::InteractionResponseType;
+
+/// How long we wait for the user to pick a block, in seconds
+const PICK_TIME: u64 = 60;
+
+/// Discord allows up to 25 options in a select menu
+const MAX_OPTIONS: usize = 25;
+
+/// Select menu option labels are capped at 100 chars
+const LABEL_LIMIT: usize = 100;
+
+/// A label for a block in the picker, its first line that says something
+fn label(index: usize, block: &str) -> String {
+    let first_line = block
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("");
+    let label = format!("{}. {}", index + 1, first_line);
+
+    match label.char_indices().nth(LABEL_LIMIT - 1) {
+        Some((end, _)) => format!("{}…", &label[..end]),
+        None => label,
+    }
+}
+
+/// Asks the user which of `blocks` to run, privately
+///
+/// Returns None when they didn't pick one in time
+async fn pick_block(
+    ctx: poise::ApplicationContext<'_, crate::Data, crate::Error>,
+    blocks: &[String],
+) -> Result<Option<usize>, Error> {
+    let handle = poise::send_application_reply(ctx, |m| {
+        m.content("That message has several code blocks, which one should I run?")
+            .ephemeral(true)
+            .components(|c| {
+                c.create_action_row(|r| {
+                    r.create_select_menu(|s| {
+                        s.custom_id("code-block")
+                            .placeholder("Pick a code block")
+                            .options(|o| {
+                                for (index, block) in blocks.iter().enumerate().take(MAX_OPTIONS) {
+                                    o.create_option(|opt| {
+                                        opt.label(label(index, block)).value(index)
+                                    });
+                                }
+                                o
+                            })
+                    })
+                })
+            })
+    })
+    .await?;
+    let picker = handle.message().await?;
+
+    let interaction = picker
+        .await_component_interaction(ctx.discord)
+        .author_id(ctx.interaction.unwrap().user.id)
+        .timeout(Duration::from_secs(PICK_TIME))
+        .await;
+    let interaction = match interaction {
+        Some(interaction) => interaction,
+        None => return Ok(None),
+    };
+
+    let index = interaction
+        .data
+        .values
+        .first()
+        .and_then(|value| value.parse::<usize>().ok())
+        .filter(|index| *index < blocks.len());
+
+    // Swap the picker for what we're doing, so it can't be used twice
+    interaction
+        .create_interaction_response(&ctx.discord.http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| {
+                    d.content(match index {
+                        Some(index) => format!("Running block {}", index + 1),
+                        None => String::from("That's not one of the blocks"),
+                    })
+                    .components(|c| c)
+                })
+        })
+        .await?;
+
+    Ok(index)
+}
+
+/// Runs the Rust code in a message
+#[poise::command(context_menu_command = "Run this code")]
+pub async fn run_message(
+    ctx: poise::ApplicationContext<'_, crate::Data, crate::Error>,
+    #[description = "Message with the code to run"] message: serenity::Message,
+) -> Result<(), Error> {
+    let blocks = rust_blocks(&message.content);
+
+    let index = match blocks.len() {
+        0 => {
+            poise::send_application_reply(ctx, |m| {
+                m.content("That message has no Rust code blocks in it")
+                    .ephemeral(true)
+            })
+            .await?;
+            return Ok(());
+        }
+        1 => {
+            // Running takes longer than discord waits for an answer
+            poise::send_application_reply(ctx, |m| m.content("Running it").ephemeral(true)).await?;
+            0
+        }
+        _ => match pick_block(ctx, &blocks).await? {
+            Some(index) => index,
+            None => return Ok(()),
+        },
+    };
+    let code = blocks[index].clone();
+
+    let options = RunOptions::default();
+    if !check_allowed(ctx.into(), &options).await? {
+        return Ok(());
+    }
+
+    let task = Task::Run(options.mode);
+    let outcome = execute(ctx.into(), &code, &options, task).await?;
+
+    let result = reply(
+        ctx.into(),
+        Some(&message),
+        code.clone(),
+        options,
+        task,
+        outcome,
+    )
+    .await?;
+
+    // Editing the message re-runs the code. Only its author can edit it, so
+    // only they get to re-run it, with the runs charged to them
+    let user = ctx.interaction.unwrap().user.id;
+    if message.author.id == user {
+        ctx.data.tracker.track(
+            message.id,
+            TrackedRun {
+                user,
+                result: result.id,
+                source: CodeSource::Block { index },
+                code,
+                options,
+            },
+        );
+    }
+
+    Ok(())
+}
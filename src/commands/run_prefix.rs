@@ -0,0 +1,119 @@
+use std::str::FromStr;
+
+use crate::commands::run::{check_allowed, execute, reply};
+use crate::model::codeblock::rust_blocks;
+use crate::model::options::{Edition, Mode, RunOptions};
+use crate::model::toolchain::Toolchain;
+use crate::model::tracker::{CodeSource, TrackedRun};
+use crate::model::trampoline::Task;
+use crate::Error;
+
+const USAGE: &str = "Put your code in a code block after the command, with any flags before it, \
+eg. `!run release 2018` followed by a ```rust code block";
+
+/// Parses the flags before the code block, either bare like `release 2018`
+/// or named like `mode=release edition=2018`
+fn parse_flags(flags: &str) -> Result<RunOptions, String> {
+    let mut options = RunOptions::default();
+
+    for flag in flags.split_whitespace() {
+        let unknown = || format!("I don't know the flag `{}`", flag);
+
+        match flag.split_once('=') {
+            Some(("mode", value)) => {
+                options.mode = Mode::from_str(value).map_err(|_| unknown())?;
+            }
+            Some(("edition", value)) => {
+                options.edition = Edition::from_str(value).map_err(|_| unknown())?;
+            }
+            Some(("channel" | "toolchain", value)) => {
+                options.toolchain = Toolchain::from_str(value).map_err(|_| unknown())?;
+            }
+            Some(_) => return Err(unknown()),
+            // The values of the different flags don't overlap, so bare ones
+            // can only mean one thing
+            None => {
+                if let Ok(mode) = Mode::from_str(flag) {
+                    options.mode = mode;
+                } else if let Ok(edition) = Edition::from_str(flag) {
+                    options.edition = edition;
+                } else if let Ok(toolchain) = Toolchain::from_str(flag) {
+                    options.toolchain = toolchain;
+                } else {
+                    return Err(unknown());
+                }
+            }
+        }
+    }
+
+    Ok(options)
+}
+
+/// Parses everything after the command name into the options and the code
+/// to run
+pub fn parse_input(input: &str) -> Result<(RunOptions, String), String> {
+    let (flags, rest) = input.split_at(input.find("```").unwrap_or(input.len()));
+
+    let mut options = parse_flags(flags)?;
+    let code = match rust_blocks(rest).into_iter().next() {
+        Some(code) => code,
+        None => return Err(String::from(USAGE)),
+    };
+
+    // Miri only comes with nightly
+    if Task::Run(options.mode).needs_nightly() {
+        options.toolchain = Toolchain::Nightly;
+    }
+
+    Ok((options, code))
+}
+
+/// Runs the code block in your message, like the original RustBot did
+///
+/// Flags for the mode, edition and channel go before the code block
+#[poise::command(prefix_command)]
+pub async fn run_prefix(
+    ctx: poise::PrefixContext<'_, crate::Data, crate::Error>,
+    #[rest] input: Option<String>,
+) -> Result<(), Error> {
+    let (options, code) = match parse_input(&input.unwrap_or_default()) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            ctx.msg.reply(ctx.discord, e).await?;
+            return Ok(());
+        }
+    };
+
+    let task = Task::Run(options.mode);
+    if !check_allowed(ctx.into(), &options).await? {
+        return Ok(());
+    }
+
+    let outcome = execute(ctx.into(), &code, &options, task).await?;
+
+    let result = reply(
+        ctx.into(),
+        Some(ctx.msg),
+        code.clone(),
+        options,
+        task,
+        outcome,
+    )
+    .await?;
+
+    // Editing the message re-runs the code
+    ctx.data.tracker.track(
+        ctx.msg.id,
+        TrackedRun {
+            user: ctx.msg.author.id,
+            result: result.id,
+            source: CodeSource::Prefix {
+                invocation: format!("{}{}", ctx.prefix, ctx.invoked_command_name),
+            },
+            code,
+            options,
+        },
+    );
+
+    Ok(())
+}
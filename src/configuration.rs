@@ -1,11 +1,23 @@
 use crate::model::configurable::*;
 
-/// Sets the container image to pull
+/// Sets the container image to pull, this is the stable toolchain
 pub const CONTAINER_IMAGE: &ConfigurableItem<&str> = &ConfigurableItem {
     environment_variable: "CONTAINER_IMAGE",
     default_value: "ghcr.io/theconner/rustbot-runner:latest",
 };
 
+/// Container image with the beta toolchain, leave empty to disable beta
+pub const CONTAINER_IMAGE_BETA: &ConfigurableItem<&str> = &ConfigurableItem {
+    environment_variable: "CONTAINER_IMAGE_BETA",
+    default_value: "",
+};
+
+/// Container image with the nightly toolchain, leave empty to disable nightly
+pub const CONTAINER_IMAGE_NIGHTLY: &ConfigurableItem<&str> = &ConfigurableItem {
+    environment_variable: "CONTAINER_IMAGE_NIGHTLY",
+    default_value: "docker.io/rustlang/rust:nightly-slim",
+};
+
 /// Sets the maximum amount of virtual CPUs available to the child container
 pub const CONTAINER_CPU: &ConfigurableItem<&str> = &ConfigurableItem {
     environment_variable: "CONTAINER_CPU",
@@ -27,12 +39,46 @@ pub const CONTAINER_SWAP: &ConfigurableItem<&str> = &ConfigurableItem {
     default_value: "5m",
 };
 
-/// How long can a container run for?
+/// How long can a program run for, in ms? Compiling is not included
 pub const CONTAINER_MAX_RUNTIME: &ConfigurableItem<u64> = &ConfigurableItem {
     environment_variable: "CONTAINER_MAX_RUNTIME",
     default_value: 5000,
 };
 
+/// How long can compiling a program take, in ms?
+pub const CONTAINER_MAX_COMPILE_TIME: &ConfigurableItem<u64> = &ConfigurableItem {
+    environment_variable: "CONTAINER_MAX_COMPILE_TIME",
+    default_value: 10000,
+};
+
+/// Container image Miri runs in, leave empty to disable Miri. This needs a
+/// nightly toolchain with the miri component and its sysroot already set up
+/// with `cargo miri setup`, which the rustlang/rust nightly images don't have
+pub const CONTAINER_IMAGE_MIRI: &ConfigurableItem<&str> = &ConfigurableItem {
+    environment_variable: "CONTAINER_IMAGE_MIRI",
+    default_value: "",
+};
+
+/// How long can a program run under Miri for, in ms? Miri interprets the
+/// program, which is a lot slower than running it, and includes building
+pub const MIRI_MAX_RUNTIME: &ConfigurableItem<u64> = &ConfigurableItem {
+    environment_variable: "MIRI_MAX_RUNTIME",
+    default_value: 30000,
+};
+
+/// Sets the maximum amount of memory available to containers running Miri
+pub const MIRI_MEMORY: &ConfigurableItem<&str> = &ConfigurableItem {
+    environment_variable: "MIRI_MEMORY",
+    default_value: "1g",
+};
+
+/// Most bytes of output kept from a run, stdout and stderr together. Anything
+/// past that is dropped, what's kept is attached in full to the reply
+pub const OUTPUT_MAX_BYTES: &ConfigurableItem<u64> = &ConfigurableItem {
+    environment_variable: "OUTPUT_MAX_BYTES",
+    default_value: 1_000_000,
+};
+
 /// Tells the bot if it's running in a container this will influence flags it
 /// chooses for child containers available values: false,true
 pub const IS_RUNNING_IN_CONTAINER: &ConfigurableItem<bool> = &ConfigurableItem {
@@ -51,3 +97,79 @@ pub const CONTAINER_PIDS: &ConfigurableItem<u64> = &ConfigurableItem {
     environment_variable: "MAX_PIDS",
     default_value: 64,
 };
+
+/// Window the RATE_LIMIT_* items are counted over, in seconds
+pub const RATE_LIMIT_WINDOW: &ConfigurableItem<u64> = &ConfigurableItem {
+    environment_variable: "RATE_LIMIT_WINDOW",
+    default_value: 60,
+};
+
+/// How many runs a single user can start per window, 0 disables the limit
+pub const RATE_LIMIT_USER: &ConfigurableItem<u64> = &ConfigurableItem {
+    environment_variable: "RATE_LIMIT_USER",
+    default_value: 5,
+};
+
+/// How many runs can be started in a single channel per window, 0 disables
+/// the limit
+pub const RATE_LIMIT_CHANNEL: &ConfigurableItem<u64> = &ConfigurableItem {
+    environment_variable: "RATE_LIMIT_CHANNEL",
+    default_value: 15,
+};
+
+/// How many runs can be started in a single guild per window, 0 disables the
+/// limit
+pub const RATE_LIMIT_GUILD: &ConfigurableItem<u64> = &ConfigurableItem {
+    environment_variable: "RATE_LIMIT_GUILD",
+    default_value: 40,
+};
+
+/// How many seconds of sandbox CPU time a user gets per day (UTC), 0 disables
+/// the budget
+pub const DAILY_CPU_SECONDS: &ConfigurableItem<u64> = &ConfigurableItem {
+    environment_variable: "DAILY_CPU_SECONDS",
+    default_value: 300,
+};
+
+/// Which sandbox runs the code, available values:
+/// podman,podman-api,docker,bubblewrap
+pub const SANDBOX_BACKEND: &ConfigurableItem<&str> = &ConfigurableItem {
+    environment_variable: "SANDBOX_BACKEND",
+    default_value: "podman",
+};
+
+/// Where the bubblewrap backend finds its root filesystems, each runner image
+/// is unpacked in a directory of its own named after the image, with `/` and
+/// `:` replaced by `_`
+pub const SANDBOX_ROOTFS: &ConfigurableItem<&str> = &ConfigurableItem {
+    environment_variable: "SANDBOX_ROOTFS",
+    default_value: "/var/lib/ferris-bot/rootfs",
+};
+
+/// Podman socket used by the podman-api backend, same format as podman's own
+/// CONTAINER_HOST variable
+pub const CONTAINER_HOST: &ConfigurableItem<&str> = &ConfigurableItem {
+    environment_variable: "CONTAINER_HOST",
+    default_value: "unix:/run/podman/podman.sock",
+};
+
+/// How many idle runner containers are kept started ahead of time, so that a
+/// run doesn't have to wait for a container to boot. 0 disables the pool
+pub const POOL_SIZE: &ConfigurableItem<u64> = &ConfigurableItem {
+    environment_variable: "POOL_SIZE",
+    default_value: 2,
+};
+
+/// When used pool containers get replaced, available values:
+/// immediate: start a replacement as soon as a container is taken
+/// when-empty: refill the whole pool once the last idle container is taken
+pub const POOL_REFILL: &ConfigurableItem<&str> = &ConfigurableItem {
+    environment_variable: "POOL_REFILL",
+    default_value: "immediate",
+};
+
+/// How many jobs may run at the same time, anything above that is queued
+pub const QUEUE_MAX_PARALLEL: &ConfigurableItem<u64> = &ConfigurableItem {
+    environment_variable: "QUEUE_MAX_PARALLEL",
+    default_value: 2,
+};
@@ -4,14 +4,31 @@ use poise::serenity_prelude as serenity;
 mod commands;
 mod configuration;
 mod model;
-use crate::commands::{quiz, run};
-use crate::model::container::{get_container_settings, ContainerActions};
+use crate::commands::{
+    clippy, emit, expand, explain, fmt, quiz, rerun, run, run_message, run_prefix,
+};
+use crate::model::configurable::ConfigurableValue;
+use crate::model::container::get_container_settings;
+use crate::model::explain::ExplanationCache;
+use crate::model::limits::RateLimiter;
+use crate::model::pool::ExecutorPool;
+use crate::model::queue::JobQueue;
+use crate::model::sandbox::get_sandbox_backend;
+use crate::model::toolchain::Toolchain;
+use crate::model::tracker::RunTracker;
 use std::process::exit;
+use std::sync::Arc;
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 pub type Context<'a> = poise::Context<'a, Data, Error>;
 // User data, which is stored and accessible in all command invocations
-pub struct Data {}
+pub struct Data {
+    pub pool: Arc<ExecutorPool>,
+    pub queue: Arc<JobQueue>,
+    pub limiter: RateLimiter,
+    pub explanations: ExplanationCache,
+    pub tracker: RunTracker,
+}
 
 /// Registers or unregisters application commands in this guild or globally
 #[poise::command(prefix_command, hide_in_help)]
@@ -21,6 +38,28 @@ async fn register(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+/// Handles the events that aren't commands
+async fn listener(
+    ctx: &serenity::Context,
+    event: &poise::Event<'_>,
+    data: &Data,
+) -> Result<(), Error> {
+    match event {
+        poise::Event::InteractionCreate {
+            interaction: serenity::Interaction::MessageComponent(component),
+        } => explain::handle_button(ctx, data, component).await?,
+        poise::Event::MessageUpdate { event, .. } => rerun::handle_edit(ctx, data, event).await?,
+        poise::Event::MessageDelete {
+            channel_id,
+            deleted_message_id,
+            ..
+        } => rerun::handle_delete(ctx, data, *channel_id, *deleted_message_id).await?,
+        _ => {}
+    }
+
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() {
     dotenv().ok();
@@ -28,17 +67,71 @@ async fn main() {
     // Before anything, pull the latest container image for running rust code
     // We will use RustBot's runner image for this
     // https://github.com/TheConner/RustBot/pkgs/container/rustbot-runner
-    if let Err(e) = get_container_settings().pull_image() {
-        println!("Error pulling image: {:?}", e);
-
-        // Fail & bail
-        exit(-1);
+    let backend = match get_sandbox_backend() {
+        Ok(backend) => backend,
+        Err(e) => {
+            println!("Error selecting sandbox backend: {:?}", e);
+            exit(-1);
+        }
     };
 
+    let settings = get_container_settings();
+
+    println!("Using the {} sandbox backend", backend.name());
+    for toolchain in Toolchain::available() {
+        let toolchain_settings = toolchain.settings(&settings).unwrap();
+        println!("Pulling {} for {}", toolchain_settings.image, toolchain);
+
+        if let Err(e) = backend.pull_image(&toolchain_settings).await {
+            println!("Error pulling image: {:?}", e);
+
+            // Fail & bail
+            exit(-1);
+        };
+    }
+    if let Some(miri_settings) = settings.for_miri() {
+        println!("Pulling {} for miri", miri_settings.image);
+
+        if let Err(e) = backend.pull_image(&miri_settings).await {
+            println!("Error pulling image: {:?}", e);
+            exit(-1);
+        };
+    }
+
+    // Clean up after a previous run of the bot that didn't get to do so itself
+    match backend.reap().await {
+        Ok(0) => {}
+        Ok(count) => println!("Removed {} leftover containers", count),
+        Err(e) => println!("Error removing leftover containers: {:?}", e),
+    }
+
+    // Warm up runner containers while we connect to discord
+    let pool = ExecutorPool::new(backend, settings);
+    pool.fill();
+
+    let queue = JobQueue::new(configuration::QUEUE_MAX_PARALLEL.value() as usize);
+
     println!("Starting up...");
+
+    // !run shares its name with /run, and commands are looked up by name, so
+    // they have to be one and the same command
+    let mut run = run::run();
+    run.prefix_action = run_prefix::run_prefix().prefix_action;
+
     let framework = poise::Framework::build()
         .options(poise::FrameworkOptions {
-            commands: vec![register(), quiz::quiz(), run::run()],
+            commands: vec![
+                register(),
+                quiz::quiz(),
+                run,
+                run_message::run_message(),
+                clippy::clippy(),
+                fmt::fmt(),
+                emit::emit(),
+                expand::expand(),
+                explain::explain(),
+            ],
+            listener: |ctx, event, _framework, data| Box::pin(listener(ctx, event, data)),
             prefix_options: poise::PrefixFrameworkOptions {
                 prefix: Some("!".into()),
                 ..Default::default()
@@ -49,7 +142,17 @@ async fn main() {
         .intents(
             serenity::GatewayIntents::non_privileged() | serenity::GatewayIntents::MESSAGE_CONTENT,
         )
-        .user_data_setup(move |_ctx, _ready, _framework| Box::pin(async move { Ok(Data {}) }));
+        .user_data_setup(move |_ctx, _ready, _framework| {
+            Box::pin(async move {
+                Ok(Data {
+                    pool,
+                    queue,
+                    limiter: RateLimiter::new(),
+                    explanations: ExplanationCache::new(),
+                    tracker: RunTracker::new(),
+                })
+            })
+        });
 
     framework.run().await.unwrap();
 }
@@ -0,0 +1,79 @@
+const FENCE: &str = "```";
+
+/// Language tags people put on blocks of Rust code
+const RUST_TAGS: [&str; 2] = ["rust", "rs"];
+
+/// A fenced code block in a discord message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// The language tag after the opening fence, if there is one
+    pub language: Option<String>,
+    pub code: String,
+}
+
+/// Finds the fenced code blocks in a discord message
+pub fn code_blocks(content: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut rest = content;
+
+    while let Some(start) = rest.find(FENCE) {
+        let after = &rest[start + FENCE.len()..];
+        let end = match after.find(FENCE) {
+            Some(end) => end,
+            None => break,
+        };
+        let block = &after[..end];
+
+        // ```rust\n...``` has a language tag, ```fn main() {}``` doesn't
+        let (language, code) = match block.split_once('\n') {
+            Some((tag, code)) if !tag.contains(char::is_whitespace) => {
+                (Some(tag).filter(|tag| !tag.is_empty()), code)
+            }
+            _ => (None, block),
+        };
+        blocks.push(CodeBlock {
+            language: language.map(|tag| tag.to_ascii_lowercase()),
+            code: code.trim_matches('\n').to_string(),
+        });
+
+        rest = &after[end + FENCE.len()..];
+    }
+
+    blocks
+}
+
+/// The Rust code blocks in a message, or its untagged blocks when none are
+/// marked as Rust
+pub fn rust_blocks(content: &str) -> Vec<String> {
+    let blocks = code_blocks(content);
+    let is_rust = |block: &&CodeBlock| {
+        block
+            .language
+            .as_deref()
+            .is_some_and(|tag| RUST_TAGS.contains(&tag))
+    };
+
+    let tagged: Vec<String> = blocks
+        .iter()
+        .filter(is_rust)
+        .map(|block| block.code.clone())
+        .collect();
+    if !tagged.is_empty() {
+        return tagged;
+    }
+
+    blocks
+        .into_iter()
+        .filter(|block| block.language.is_none())
+        .map(|block| block.code)
+        .collect()
+}
+
+/// The code in a message: its first Rust code block, or the whole message
+/// when it has none
+pub fn extract_code(content: &str) -> String {
+    match rust_blocks(content).into_iter().next() {
+        Some(block) => block,
+        None => content.trim().trim_matches('`').to_string(),
+    }
+}
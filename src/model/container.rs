@@ -1,104 +1,71 @@
 use crate::configuration;
 use crate::model::configurable::ConfigurableValue;
-use std::io;
-use std::io::Error;
-use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Time on top of the compile and run budgets for getting a container going
+const STARTUP_ALLOWANCE: u64 = 5000;
 
 /// Settings for our container
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct ContainerSettings {
     pub cpu: String,
     pub memory: String,
     pub swap: String,
     pub image: String,
     pub max_runtime: u64,
+    pub max_compile_time: u64,
     pub network: String,
     pub pid_limit: u64,
 }
 
-pub trait ContainerActions {
-    fn container_command(&self) -> String;
-    fn generate_runtime_flags(&self) -> String;
-    fn pull_image(&self) -> Result<(), Error>;
-    fn invoke_command(&self, command: String) -> io::Result<std::process::Child>;
-}
-
-impl ContainerActions for ContainerSettings {
-    /// Gets the container command to use
-    /// Either podman-remote or podman
-    fn container_command(&self) -> String {
-        // Are we running this in a container?
-        if configuration::IS_RUNNING_IN_CONTAINER.value() {
-            // Invoke podman with remote socket that should be passed to the container
-            String::from("podman-remote")
-        } else {
-            // Invoke podman the default way
-            String::from("podman")
-        }
+impl ContainerSettings {
+    /// How many CPUs the container gets, as a number
+    pub fn cpus(&self) -> f64 {
+        self.cpu.parse().unwrap_or(1.0)
     }
 
-    /// Turns a ContainerSettings instance into a string of CLI args for Podman or Docker
-    fn generate_runtime_flags(&self) -> String {
-        format!(
-            "--cap-drop=ALL --security-opt=no-new-privileges --cpus={} --memory={} --network={} --pids-limit={}",
-            self.cpu, self.memory, self.network, self.pid_limit
-        )
+    /// How long a whole container may live before we kill it
+    ///
+    /// The compile and run budgets are enforced inside the sandbox, this is
+    /// just a backstop in case the container itself gets stuck
+    pub fn time_limit(&self) -> Duration {
+        Duration::from_millis(self.max_compile_time + self.max_runtime + STARTUP_ALLOWANCE)
     }
 
-    /// Pulls a container image from a registry
-    fn pull_image(&self) -> Result<(), Error> {
-        let output = Command::new(self.container_command())
-            .arg("pull")
-            .arg(&self.image)
-            .status()
-            .expect("failed to execute process");
-
-        let status = output.code().expect("No output code");
-
-        if status == 0 {
-            Ok(())
-        } else {
-            Result::Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Could not pull container image, got error code {}", status),
-            ))
-        }
+    /// These settings with the image and limits for running under Miri
+    /// instead, None if Miri isn't set up
+    pub fn for_miri(&self) -> Option<ContainerSettings> {
+        Some(ContainerSettings {
+            image: miri_image()?,
+            memory: (*configuration::MIRI_MEMORY).value(),
+            max_runtime: (*configuration::MIRI_MAX_RUNTIME).value(),
+            ..self.clone()
+        })
     }
+}
 
-    fn invoke_command(&self, command: String) -> io::Result<std::process::Child> {
-        let container_command = format!(
-            "{} run --rm {} {} {}",
-            self.container_command(),
-            self.generate_runtime_flags(),
-            self.image,
-            command
-        );
+/// Image Miri runs in, None if it isn't configured
+pub fn miri_image() -> Option<String> {
+    let image = (*configuration::CONTAINER_IMAGE_MIRI).value();
+    if image.is_empty() {
+        None
+    } else {
+        Some(image)
+    }
+}
 
-        println!("{}", container_command);
+/// Parses a size like 100m or 1g (as used by podman and docker) into bytes
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim().to_ascii_lowercase();
+    let (number, multiplier) = match size.chars().last()? {
+        'b' => (&size[..size.len() - 1], 1),
+        'k' => (&size[..size.len() - 1], 1024),
+        'm' => (&size[..size.len() - 1], 1024 * 1024),
+        'g' => (&size[..size.len() - 1], 1024 * 1024 * 1024),
+        _ => (size.as_str(), 1),
+    };
 
-        // Because std::command does not give me the ability to override / modify
-        // how arguments are escaped I have to do some stupid hack to make this
-        // work. For example, if I wanted to run
-        // podman run rustbot:latest ls -al
-        // this would be impossible if I did
-        //
-        //  std::process::Command::new("podman")
-        //    .args(["run", "rustbot:latest", "ls -al"])
-        //    .output()
-        //    .expect("failed to invoke container");
-        //
-        // As the ls -al would be quoted, and the container would try to execute
-        // `ls -al` which would fail. The alternative is to seperate "ls", "-al"
-        // which would also fail as the container would run `ls` then `-al`
-        // ... what a stupid design
-        // So instead of embracing the safety this API gives you, i'm just invoking
-        // a shell with a payload I deem as safe
-        Command::new("sh")
-            .args(["-c", container_command.as_str()])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-    }
+    number.parse::<u64>().ok().map(|n| n * multiplier)
 }
 
 /// Gets the default container settings
@@ -109,6 +76,7 @@ pub fn get_container_settings() -> ContainerSettings {
         memory: (*configuration::CONTAINER_MEMORY).value(),
         swap: (*configuration::CONTAINER_SWAP).value(),
         max_runtime: (*configuration::CONTAINER_MAX_RUNTIME).value(),
+        max_compile_time: (*configuration::CONTAINER_MAX_COMPILE_TIME).value(),
         network: (*configuration::CONTAINER_NETWORK).value(),
         pid_limit: (*configuration::CONTAINER_PIDS).value(),
     }
@@ -0,0 +1,192 @@
+use serde::Deserialize;
+use serenity::utils::Colour;
+
+/// A diagnostic as emitted by rustc and clippy with --message-format=json
+#[derive(Debug, Clone, Deserialize)]
+pub struct Diagnostic {
+    pub message: String,
+    pub code: Option<DiagnosticCode>,
+    pub level: String,
+    pub spans: Vec<DiagnosticSpan>,
+    /// Notes and help attached to the diagnostic
+    pub children: Vec<Diagnostic>,
+}
+
+/// The error code or lint name of a diagnostic, eg. E0308 or clippy::needless_return
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiagnosticCode {
+    pub code: String,
+}
+
+/// A piece of the code a diagnostic points at
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiagnosticSpan {
+    pub line_start: usize,
+    pub column_start: usize,
+    pub is_primary: bool,
+    pub label: Option<String>,
+    /// What rustc suggests replacing the span with
+    pub suggested_replacement: Option<String>,
+    /// The lines of code the span is on
+    pub text: Vec<SpanLine>,
+}
+
+/// A line of code a span is on, with the columns it covers counted from 1
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpanLine {
+    pub text: String,
+    pub highlight_start: usize,
+    pub highlight_end: usize,
+}
+
+/// How bad a diagnostic is, in the order they are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    /// Colour of an embed whose worst diagnostic has this severity
+    pub fn colour(&self) -> Colour {
+        match self {
+            Self::Error => Colour::RED,
+            Self::Warning => Colour::GOLD,
+            Self::Note => Colour::BLUE,
+        }
+    }
+
+    pub fn emoji(&self) -> char {
+        match self {
+            Self::Error => '❌',
+            Self::Warning => '⚠',
+            Self::Note => 'ℹ',
+        }
+    }
+}
+
+/// One line of cargo's JSON output, we only care about the compiler messages
+#[derive(Deserialize)]
+struct CargoMessage {
+    reason: String,
+    message: Option<Diagnostic>,
+}
+
+impl Diagnostic {
+    /// Parses the diagnostics out of cargo's --message-format=json output
+    ///
+    /// Summaries like "aborting due to previous error" point at no code and
+    /// are left out
+    pub fn from_cargo_output(stdout: &str) -> Vec<Diagnostic> {
+        stdout
+            .lines()
+            .filter_map(|line| serde_json::from_str::<CargoMessage>(line).ok())
+            .filter(|message| message.reason == "compiler-message")
+            .filter_map(|message| message.message)
+            .filter(|diagnostic| !diagnostic.spans.is_empty())
+            .collect()
+    }
+
+    /// Splits the diagnostics of rustc's --error-format=json output off the
+    /// rest of stderr, which also has whatever the program itself printed
+    pub fn from_json_lines(stderr: &str) -> (Vec<Diagnostic>, String) {
+        let mut diagnostics = Vec::new();
+        let mut rest = String::new();
+
+        for line in stderr.lines() {
+            match serde_json::from_str::<Diagnostic>(line) {
+                // Summaries point at no code, we say the same with our title
+                Ok(diagnostic) if diagnostic.spans.is_empty() => {}
+                Ok(diagnostic) => diagnostics.push(diagnostic),
+                Err(_) => {
+                    rest.push_str(line);
+                    rest.push('\n');
+                }
+            }
+        }
+
+        (diagnostics, rest)
+    }
+
+    /// The first line of the diagnostic as rustc would print it, eg.
+    /// error[E0382]: borrow of moved value: `x`
+    pub fn headline(&self) -> String {
+        match &self.code {
+            Some(code) => format!("{}[{}]: {}", self.level, code.code, self.message),
+            None => format!("{}: {}", self.level, self.message),
+        }
+    }
+
+    /// The line of code the diagnostic is about, with its primary span
+    /// underlined like rustc does:
+    ///
+    /// ```text
+    /// 5 | println!("{}", x);
+    ///   |                ^ value borrowed here after move
+    /// ```
+    pub fn snippet(&self) -> Option<String> {
+        let span = self.primary_span()?;
+        let line = span.text.first()?;
+
+        // Leave out the indentation, it only takes up space
+        let code = line.text.trim_start();
+        let indent = line.text.chars().count() - code.chars().count();
+        let start = line.highlight_start.saturating_sub(1 + indent);
+        let width = line
+            .highlight_end
+            .saturating_sub(line.highlight_start)
+            .max(1);
+
+        let number = span.line_start.to_string();
+        let gutter = " ".repeat(number.len());
+        let mut snippet = format!(
+            "{} | {}\n{} | {}{}",
+            number,
+            code,
+            gutter,
+            " ".repeat(start),
+            "^".repeat(width)
+        );
+        if let Some(label) = &span.label {
+            snippet.push(' ');
+            snippet.push_str(label);
+        }
+
+        Some(snippet)
+    }
+
+    /// rustc's help for fixing the diagnostic, with the replacements it suggests
+    pub fn suggestions(&self) -> Vec<String> {
+        self.children
+            .iter()
+            .filter(|child| child.level == "help")
+            .map(|child| {
+                let replacement = child
+                    .spans
+                    .iter()
+                    .find_map(|span| span.suggested_replacement.as_ref());
+                match replacement {
+                    Some(replacement) => format!("help: {}: `{}`", child.message, replacement),
+                    None => format!("help: {}", child.message),
+                }
+            })
+            .collect()
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self.level.as_str() {
+            "error" | "error: internal compiler error" => Severity::Error,
+            "warning" => Severity::Warning,
+            _ => Severity::Note,
+        }
+    }
+
+    /// The span the diagnostic is mainly about
+    pub fn primary_span(&self) -> Option<&DiagnosticSpan> {
+        self.spans
+            .iter()
+            .find(|span| span.is_primary)
+            .or_else(|| self.spans.first())
+    }
+}
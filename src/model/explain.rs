@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use serenity::{
+    builder::{CreateActionRow, CreateButton},
+    model::interactions::message_component::ButtonStyle,
+};
+
+/// Custom ids of explain buttons start with this, followed by the error code
+const BUTTON_PREFIX: &str = "explain:";
+
+/// Discord allows up to 5 buttons per action row
+const MAX_BUTTONS: usize = 5;
+
+/// An error code of rustc, eg. E0382
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ErrorCode(u16);
+
+#[derive(Debug)]
+pub struct ParseErrorCodeError(pub String);
+
+impl fmt::Display for ParseErrorCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is not an error code, they look like E0382", self.0)
+    }
+}
+
+impl StdError for ParseErrorCodeError {}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "E{:04}", self.0)
+    }
+}
+
+impl FromStr for ErrorCode {
+    type Err = ParseErrorCodeError;
+
+    /// Parses E0382, but also e0382 and 0382 as people will type those too
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let digits = trimmed.strip_prefix(['E', 'e']).unwrap_or(trimmed);
+
+        if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(ParseErrorCodeError(s.to_string()));
+        }
+        digits
+            .parse()
+            .map(ErrorCode)
+            .map_err(|_| ParseErrorCodeError(s.to_string()))
+    }
+}
+
+impl ErrorCode {
+    /// Finds the error codes mentioned in some compiler output, in the order
+    /// they first appear
+    pub fn find_all(output: &str) -> Vec<ErrorCode> {
+        let bytes = output.as_bytes();
+        let mut codes = Vec::new();
+
+        for (i, _) in output.match_indices('E') {
+            // Only whole words, so we don't pick up hashes and the like
+            let starts_word = i == 0 || !bytes[i - 1].is_ascii_alphanumeric();
+            let ends_word = bytes.get(i + 5).is_none_or(|b| !b.is_ascii_alphanumeric());
+            if !starts_word || !ends_word {
+                continue;
+            }
+
+            if let Some(Ok(code)) = output.get(i..i + 5).map(str::parse) {
+                if !codes.contains(&code) {
+                    codes.push(code);
+                }
+            }
+        }
+
+        codes
+    }
+
+    /// The code a button made by `action_row` stands for
+    pub fn from_custom_id(custom_id: &str) -> Option<ErrorCode> {
+        custom_id.strip_prefix(BUTTON_PREFIX)?.parse().ok()
+    }
+
+    /// Link to the explanation in the error code index
+    pub fn url(&self) -> String {
+        format!("https://doc.rust-lang.org/error_codes/{}.html", self)
+    }
+
+    fn button(&self) -> CreateButton {
+        let mut b = CreateButton::default();
+        b.custom_id(format!("{}{}", BUTTON_PREFIX, self));
+        b.emoji('📖');
+        b.label(format!("Explain {}", self));
+        b.style(ButtonStyle::Secondary);
+        b
+    }
+
+    /// A row of explain buttons for the first few of `codes`
+    pub fn action_row(codes: &[ErrorCode]) -> CreateActionRow {
+        let mut ar = CreateActionRow::default();
+        for code in codes.iter().take(MAX_BUTTONS) {
+            ar.add_button(code.button());
+        }
+        ar
+    }
+}
+
+/// Explanations we already got from rustc, which never change for a toolchain.
+/// Codes rustc doesn't know are remembered as None
+#[derive(Default)]
+pub struct ExplanationCache {
+    explanations: Mutex<HashMap<ErrorCode, Option<String>>>,
+}
+
+impl ExplanationCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The explanation for `code`, if we've asked rustc about it before
+    pub fn get(&self, code: ErrorCode) -> Option<Option<String>> {
+        self.explanations.lock().unwrap().get(&code).cloned()
+    }
+
+    pub fn insert(&self, code: ErrorCode, explanation: Option<String>) {
+        self.explanations.lock().unwrap().insert(code, explanation);
+    }
+}
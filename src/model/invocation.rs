@@ -0,0 +1,90 @@
+use std::fmt;
+use tokio::process::Command;
+
+/// A typed container engine invocation
+///
+/// Builds up an argument vector for `tokio::process::Command` piece by piece,
+/// so that config values and payloads are always passed as single arguments
+/// and never go through a shell
+#[derive(Clone, Debug)]
+pub struct ContainerInvocation {
+    program: String,
+    subcommand: String,
+    flags: Vec<String>,
+    target: Option<String>,
+    command: Vec<String>,
+}
+
+impl ContainerInvocation {
+    /// Starts a new invocation of `program` (eg. podman) with `subcommand` (eg. run)
+    pub fn new(program: impl Into<String>, subcommand: impl Into<String>) -> Self {
+        ContainerInvocation {
+            program: program.into(),
+            subcommand: subcommand.into(),
+            flags: Vec::new(),
+            target: None,
+            command: Vec::new(),
+        }
+    }
+
+    /// Adds a single flag, placed before the image or container
+    pub fn flag(mut self, flag: impl Into<String>) -> Self {
+        self.flags.push(flag.into());
+        self
+    }
+
+    /// Adds several flags, placed before the image or container
+    pub fn flags<I, S>(mut self, flags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.flags.extend(flags.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets the image the invocation operates on, eg. for run or pull
+    pub fn image(mut self, image: impl Into<String>) -> Self {
+        self.target = Some(image.into());
+        self
+    }
+
+    /// Sets the container the invocation operates on, eg. for exec or rm
+    pub fn container(mut self, id: impl Into<String>) -> Self {
+        self.target = Some(id.into());
+        self
+    }
+
+    /// Sets the command to run inside the container, one element per argument
+    pub fn command<I, S>(mut self, command: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.command = command.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// The full argument vector passed to the program, in order
+    pub fn args(&self) -> Vec<&str> {
+        let mut args = vec![self.subcommand.as_str()];
+        args.extend(self.flags.iter().map(String::as_str));
+        args.extend(self.target.as_deref());
+        args.extend(self.command.iter().map(String::as_str));
+        args
+    }
+
+    /// Turns the invocation into a `Command` ready to be spawned
+    pub fn build(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(self.args());
+        command
+    }
+}
+
+impl fmt::Display for ContainerInvocation {
+    /// Human readable form of the invocation, for logging only
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.program, self.args().join(" "))
+    }
+}
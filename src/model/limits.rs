@@ -0,0 +1,172 @@
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::configuration;
+use crate::model::configurable::ConfigurableValue;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// What a rate limit is counted against
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Scope {
+    User(UserId),
+    Channel(ChannelId),
+    Guild(GuildId),
+}
+
+/// Why someone isn't allowed to run code right now, and when they can again
+#[derive(Debug)]
+pub enum Limited {
+    User { retry_at: SystemTime },
+    Channel { retry_at: SystemTime },
+    Guild { retry_at: SystemTime },
+    CpuQuota { retry_at: SystemTime },
+}
+
+impl Limited {
+    /// When the limit is lifted again
+    pub fn retry_at(&self) -> SystemTime {
+        match self {
+            Self::User { retry_at }
+            | Self::Channel { retry_at }
+            | Self::Guild { retry_at }
+            | Self::CpuQuota { retry_at } => *retry_at,
+        }
+    }
+}
+
+impl fmt::Display for Limited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::User { .. } => write!(f, "You are running code too often"),
+            Self::Channel { .. } => write!(f, "Too much code is being run in this channel"),
+            Self::Guild { .. } => write!(f, "Too much code is being run on this server"),
+            Self::CpuQuota { .. } => write!(f, "You used up your CPU time for today"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct LimiterState {
+    /// Start times of the runs within the window, oldest first
+    runs: HashMap<Scope, VecDeque<Instant>>,
+    /// CPU seconds used by every user, and the day they were used on
+    cpu: HashMap<UserId, (u64, f64)>,
+}
+
+/// Keeps track of who ran code when, to stop anyone from hogging the sandbox
+///
+/// Every limit can be turned off by setting it to 0.
+pub struct RateLimiter {
+    window: Duration,
+    user_limit: usize,
+    channel_limit: usize,
+    guild_limit: usize,
+    daily_cpu_seconds: f64,
+    state: Mutex<LimiterState>,
+}
+
+impl RateLimiter {
+    /// Creates a rate limiter with the limits from the configuration
+    pub fn new() -> Self {
+        RateLimiter {
+            window: Duration::from_secs(configuration::RATE_LIMIT_WINDOW.value()),
+            user_limit: configuration::RATE_LIMIT_USER.value() as usize,
+            channel_limit: configuration::RATE_LIMIT_CHANNEL.value() as usize,
+            guild_limit: configuration::RATE_LIMIT_GUILD.value() as usize,
+            daily_cpu_seconds: configuration::DAILY_CPU_SECONDS.value() as f64,
+            state: Mutex::new(LimiterState::default()),
+        }
+    }
+
+    /// Checks every limit for a new run, and counts the run if it's allowed
+    pub fn check(
+        &self,
+        user: UserId,
+        channel: ChannelId,
+        guild: Option<GuildId>,
+    ) -> Result<(), Limited> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        if self.daily_cpu_seconds > 0.0 {
+            if let Some((day, used)) = state.cpu.get(&user) {
+                if *day == today() && *used >= self.daily_cpu_seconds {
+                    let tomorrow =
+                        UNIX_EPOCH + Duration::from_secs((today() + 1) * SECONDS_PER_DAY);
+                    return Err(Limited::CpuQuota { retry_at: tomorrow });
+                }
+            }
+        }
+
+        let mut scopes = vec![
+            (Scope::User(user), self.user_limit),
+            (Scope::Channel(channel), self.channel_limit),
+        ];
+        if let Some(guild) = guild {
+            scopes.push((Scope::Guild(guild), self.guild_limit));
+        }
+        scopes.retain(|(_, limit)| *limit > 0);
+
+        // Forget the runs that left the window, and the scopes left without
+        // any, so only those who ran something lately are remembered
+        let window = self.window;
+        state.runs.retain(|_, runs| {
+            while runs
+                .front()
+                .is_some_and(|start| now.duration_since(*start) >= window)
+            {
+                runs.pop_front();
+            }
+            !runs.is_empty()
+        });
+
+        // Check everything before counting anything, a refused run shouldn't
+        // use up any of the limits
+        for (scope, limit) in &scopes {
+            let runs = match state.runs.get(scope) {
+                Some(runs) => runs,
+                None => continue,
+            };
+
+            if runs.len() >= *limit {
+                let retry_at = SystemTime::now() + (runs[0] + self.window - now);
+                return Err(match scope {
+                    Scope::User(_) => Limited::User { retry_at },
+                    Scope::Channel(_) => Limited::Channel { retry_at },
+                    Scope::Guild(_) => Limited::Guild { retry_at },
+                });
+            }
+        }
+
+        for (scope, _) in scopes {
+            state.runs.entry(scope).or_default().push_back(now);
+        }
+
+        Ok(())
+    }
+
+    /// Adds the CPU time of a finished run to the user's daily budget
+    pub fn charge(&self, user: UserId, cpu_seconds: f64) {
+        let mut state = self.state.lock().unwrap();
+        let today = today();
+
+        // Yesterday's usage doesn't count anymore
+        state.cpu.retain(|_, (day, _)| *day == today);
+
+        let (_, used) = state.cpu.entry(user).or_insert((today, 0.0));
+        *used += cpu_seconds;
+    }
+}
+
+/// Days since the epoch, so the CPU budget resets at midnight UTC
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        / SECONDS_PER_DAY
+}
@@ -1,4 +1,18 @@
+pub mod codeblock;
 pub mod configurable;
 pub mod container;
+pub mod diagnostic;
+pub mod explain;
+pub mod invocation;
+pub mod limits;
+pub mod options;
+pub mod outcome;
+pub mod pool;
 pub mod question;
+pub mod queue;
 pub mod runnable;
+pub mod sandbox;
+pub mod snippet;
+pub mod toolchain;
+pub mod tracker;
+pub mod trampoline;
@@ -0,0 +1,176 @@
+use std::fmt;
+
+use crate::model::container::miri_image;
+use crate::model::toolchain::Toolchain;
+
+/// Rust editions code can be compiled with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter)]
+pub enum Edition {
+    #[name = "2015"]
+    E2015,
+    #[name = "2018"]
+    E2018,
+    #[default]
+    #[name = "2021"]
+    E2021,
+    #[name = "2024"]
+    E2024,
+}
+
+impl fmt::Display for Edition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::E2015 => write!(f, "2015"),
+            Self::E2018 => write!(f, "2018"),
+            Self::E2021 => write!(f, "2021"),
+            Self::E2024 => write!(f, "2024"),
+        }
+    }
+}
+
+/// What to do with the code, like the playground's build modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter)]
+pub enum Mode {
+    /// Build without optimizations and run
+    #[default]
+    #[name = "debug"]
+    Debug,
+    /// Build with optimizations and run
+    #[name = "release"]
+    Release,
+    /// Only check that the code builds
+    #[name = "build"]
+    Build,
+    /// Build and run the #[test] functions
+    #[name = "test"]
+    Test,
+    /// Interpret with Miri to catch undefined behaviour
+    #[name = "miri"]
+    Miri,
+}
+
+impl Mode {
+    /// Whether this needs tools that only come with nightly
+    pub fn needs_nightly(&self) -> bool {
+        *self == Self::Miri
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Debug => write!(f, "debug"),
+            Self::Release => write!(f, "release"),
+            Self::Build => write!(f, "build"),
+            Self::Test => write!(f, "test"),
+            Self::Miri => write!(f, "miri"),
+        }
+    }
+}
+
+/// How much the compiler optimizes, for when the code isn't run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter)]
+pub enum Profile {
+    #[default]
+    #[name = "debug"]
+    Debug,
+    #[name = "release"]
+    Release,
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Debug => write!(f, "debug"),
+            Self::Release => write!(f, "release"),
+        }
+    }
+}
+
+/// What the compiler can show of the code, like the playground's export menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter)]
+pub enum Emit {
+    #[default]
+    #[name = "assembly (intel)"]
+    AsmIntel,
+    #[name = "assembly (at&t)"]
+    AsmAtt,
+    #[name = "llvm ir"]
+    LlvmIr,
+    #[name = "mir"]
+    Mir,
+    #[name = "hir"]
+    Hir,
+}
+
+impl Emit {
+    /// Name of the file the output is attached as when it's too long
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Self::AsmIntel | Self::AsmAtt => "main.s",
+            Self::LlvmIr => "main.ll",
+            Self::Mir => "main.mir",
+            Self::Hir => "main.hir",
+        }
+    }
+
+    /// Language to highlight the output as
+    pub fn syntax(&self) -> &'static str {
+        match self {
+            Self::AsmIntel | Self::AsmAtt => "x86asm",
+            Self::LlvmIr => "llvm",
+            Self::Mir | Self::Hir => "rust",
+        }
+    }
+
+    /// Whether this needs unstable compiler flags
+    pub fn needs_nightly(&self) -> bool {
+        *self == Self::Hir
+    }
+}
+
+impl fmt::Display for Emit {
+    /// Name of the output as the trampoline knows it
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AsmIntel => write!(f, "asm-intel"),
+            Self::AsmAtt => write!(f, "asm-att"),
+            Self::LlvmIr => write!(f, "llvm-ir"),
+            Self::Mir => write!(f, "mir"),
+            Self::Hir => write!(f, "hir"),
+        }
+    }
+}
+
+/// Everything the user can choose about how their code is run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RunOptions {
+    pub toolchain: Toolchain,
+    pub edition: Edition,
+    pub mode: Mode,
+}
+
+impl RunOptions {
+    /// Why code can't be run with these options on this bot, None if it can
+    pub fn unavailable(&self) -> Option<String> {
+        // Miri has an image of its own, rather than the toolchain's
+        if self.mode == Mode::Miri {
+            return match miri_image() {
+                Some(_) => None,
+                None => Some(String::from("Miri is not set up on this bot")),
+            };
+        }
+
+        match self.toolchain.image() {
+            Some(_) => None,
+            None => Some(format!("The {} toolchain is not available", self.toolchain)),
+        }
+    }
+}
+
+impl fmt::Display for RunOptions {
+    /// Short summary for the result embed, eg. "stable · 2021 edition"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} · {} edition", self.toolchain, self.edition)
+    }
+}
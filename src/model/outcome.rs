@@ -0,0 +1,333 @@
+use serenity::utils::Colour;
+use std::fmt;
+use std::io;
+use std::io::Error;
+use std::os::unix::process::ExitStatusExt;
+use std::time::Duration;
+
+use crate::model::sandbox::output::SandboxOutput;
+use crate::model::trampoline::REPORT_PREFIX;
+
+/// Exit code podman and docker use when the container itself failed to run
+const ENGINE_FAILURE_CODES: [i32; 3] = [125, 126, 127];
+
+/// Shells report death by signal N as exit code 128 + N
+const SIGNAL_EXIT_BASE: i32 = 128;
+
+/// How Miri starts a report of undefined behaviour
+const UB_REPORT_START: &str = "error: Undefined Behavior";
+
+/// How Miri ends its output after an error
+const UB_REPORT_END: &str = "error: aborting due to";
+
+/// How cargo says the program didn't compile, when run under Miri
+const MIRI_COMPILE_ERROR: &str = "error: could not compile `playground`";
+
+/// The phases of a run, each with their own time budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Compile,
+    Run,
+}
+
+/// How a run of some code ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutionStatus {
+    /// Compiled and exited with 0
+    Success,
+    /// rustc rejected the code
+    CompileError,
+    /// The program exited with a non-zero exit code
+    RuntimeError { exit_code: i32 },
+    /// Some #[test] functions failed
+    TestsFailed,
+    /// The program panicked
+    Panic,
+    /// The program was killed by a signal, eg. a segfault
+    Signal { signal: i32 },
+    /// The program ran out of memory and the OOM killer stopped it
+    OutOfMemory,
+    /// Miri caught the program doing something undefined
+    UndefinedBehaviour,
+    /// The program took too long, in the given phase if we know it
+    Timeout { phase: Option<Phase> },
+    /// Something went wrong on our end and the code never ran properly
+    SandboxFailure { reason: String },
+}
+
+/// How a single #[test] function went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+impl TestStatus {
+    pub fn emoji(&self) -> char {
+        match self {
+            Self::Passed => '✅',
+            Self::Failed => '❌',
+            Self::Ignored => '⏭',
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    pub name: String,
+    pub status: TestStatus,
+}
+
+/// Everything we know about a run of some code
+#[derive(Debug, Clone)]
+pub struct ExecutionOutcome {
+    pub status: ExecutionStatus,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration: Duration,
+    /// Whether the output was longer than we keep, and got cut short
+    pub truncated: bool,
+}
+
+impl ExecutionOutcome {
+    /// Classifies the result of running the trampoline in a sandbox
+    pub fn from_result(result: Result<SandboxOutput, Error>, duration: Duration) -> Self {
+        let output = match result {
+            Ok(output) => output,
+            Err(e) => {
+                let status = match e.kind() {
+                    io::ErrorKind::TimedOut => ExecutionStatus::Timeout { phase: None },
+                    _ => ExecutionStatus::SandboxFailure {
+                        reason: e.to_string(),
+                    },
+                };
+
+                return ExecutionOutcome {
+                    status,
+                    stdout: String::new(),
+                    stderr: String::new(),
+                    duration,
+                    truncated: false,
+                };
+            }
+        };
+
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        let (stderr, report) = take_report(&String::from_utf8_lossy(&output.stderr));
+
+        let status = match report {
+            Some(status) => status,
+            None => Self::from_exit(output.status.code(), output.status.signal(), &stderr),
+        };
+
+        ExecutionOutcome {
+            status,
+            stdout,
+            stderr,
+            duration,
+            truncated: output.truncated,
+        }
+    }
+
+    /// Classifies a run the trampoline didn't report on by how the container
+    /// exited
+    fn from_exit(code: Option<i32>, signal: Option<i32>, stderr: &str) -> ExecutionStatus {
+        match (code, signal) {
+            (Some(0), _) => ExecutionStatus::Success,
+            (_, Some(signal)) => ExecutionStatus::Signal { signal },
+            (Some(code), _) if ENGINE_FAILURE_CODES.contains(&code) => {
+                ExecutionStatus::SandboxFailure {
+                    reason: format!("Container engine exited with {}", code),
+                }
+            }
+            (Some(code), _) if code > SIGNAL_EXIT_BASE => ExecutionStatus::Signal {
+                signal: code - SIGNAL_EXIT_BASE,
+            },
+            (Some(code), _) => Self::from_program_exit(code, stderr),
+            (None, None) => ExecutionStatus::SandboxFailure {
+                reason: String::from("Process ended without an exit code"),
+            },
+        }
+    }
+
+    /// Classifies a program that exited with `code` by itself
+    fn from_program_exit(code: i32, stderr: &str) -> ExecutionStatus {
+        if code == 0 {
+            ExecutionStatus::Success
+        } else if stderr.contains("panicked at") {
+            ExecutionStatus::Panic
+        } else {
+            ExecutionStatus::RuntimeError { exit_code: code }
+        }
+    }
+
+    /// Tells apart the ways a run under Miri failed, which the trampoline
+    /// only reports as the exit code of cargo
+    pub fn classify_miri(&mut self) {
+        if !matches!(
+            self.status,
+            ExecutionStatus::RuntimeError { .. } | ExecutionStatus::Panic
+        ) {
+            return;
+        }
+
+        if self.ub_report().is_some() {
+            self.status = ExecutionStatus::UndefinedBehaviour;
+        } else if self
+            .stderr
+            .lines()
+            .any(|line| line.starts_with(MIRI_COMPILE_ERROR))
+        {
+            self.status = ExecutionStatus::CompileError;
+        }
+    }
+
+    /// Results of the #[test] functions, as reported by the test harness
+    pub fn test_results(&self) -> Vec<TestResult> {
+        self.stdout
+            .lines()
+            .filter_map(|line| {
+                let (name, result) = line.strip_prefix("test ")?.split_once(" ... ")?;
+                let status = match result {
+                    "ok" => TestStatus::Passed,
+                    "FAILED" => TestStatus::Failed,
+                    // Newer harnesses add the reason, eg. "ignored, slow"
+                    result if result.starts_with("ignored") => TestStatus::Ignored,
+                    _ => return None,
+                };
+
+                Some(TestResult {
+                    name: name.to_string(),
+                    status,
+                })
+            })
+            .collect()
+    }
+
+    /// Splits Miri's undefined behaviour report off the rest of stderr
+    ///
+    /// Miri reports last, so anything like a report the program printed
+    /// itself comes before the real one
+    pub fn ub_report(&self) -> Option<(String, String)> {
+        let start = self.stderr.rfind(UB_REPORT_START)?;
+        let (before, report) = self.stderr.split_at(start);
+
+        // Miri finishes with a summary of how many errors there were
+        let (report, after) = match report.find(UB_REPORT_END) {
+            Some(end) => report.split_at(end),
+            None => (report, ""),
+        };
+
+        Some((
+            format!("{}{}", before, after).trim().to_string(),
+            report.trim().to_string(),
+        ))
+    }
+}
+
+/// Splits the trampoline's report off the end of stderr
+fn take_report(stderr: &str) -> (String, Option<ExecutionStatus>) {
+    let trimmed = stderr.trim_end();
+    let (rest, last_line) = match trimmed.rsplit_once('\n') {
+        Some((rest, last_line)) => (rest, last_line),
+        None => ("", trimmed),
+    };
+
+    let status = match last_line.strip_prefix(REPORT_PREFIX) {
+        Some("compile-error") => ExecutionStatus::CompileError,
+        Some("tests-failed") => ExecutionStatus::TestsFailed,
+        Some("out-of-memory") => ExecutionStatus::OutOfMemory,
+        Some("timeout compile") => ExecutionStatus::Timeout {
+            phase: Some(Phase::Compile),
+        },
+        Some("timeout run") => ExecutionStatus::Timeout {
+            phase: Some(Phase::Run),
+        },
+        // How the program exited, as opposed to the container around it
+        Some(report) => match report.split_once(' ') {
+            Some(("exit", code)) => match code.parse() {
+                Ok(code) => ExecutionOutcome::from_program_exit(code, rest),
+                Err(_) => return (stderr.to_string(), None),
+            },
+            Some(("signal", signal)) => match signal.parse() {
+                Ok(signal) => ExecutionStatus::Signal { signal },
+                Err(_) => return (stderr.to_string(), None),
+            },
+            _ => return (stderr.to_string(), None),
+        },
+        None => return (stderr.to_string(), None),
+    };
+
+    (rest.to_string(), Some(status))
+}
+
+impl ExecutionStatus {
+    /// Colour of the result embed
+    pub fn colour(&self) -> Colour {
+        match self {
+            Self::Success => Colour::DARK_GREEN,
+            Self::CompileError => Colour::RED,
+            Self::RuntimeError { .. } => Colour::ORANGE,
+            Self::TestsFailed => Colour::ORANGE,
+            Self::Panic => Colour::DARK_ORANGE,
+            Self::Signal { .. } => Colour::DARK_RED,
+            Self::OutOfMemory => Colour::PURPLE,
+            Self::UndefinedBehaviour => Colour::DARK_PURPLE,
+            Self::Timeout { .. } => Colour::GOLD,
+            Self::SandboxFailure { .. } => Colour::LIGHT_GREY,
+        }
+    }
+
+    pub fn emoji(&self) -> char {
+        match self {
+            Self::Success => '✅',
+            Self::CompileError => '❌',
+            Self::RuntimeError { .. } => '⚠',
+            Self::TestsFailed => '❌',
+            Self::Panic => '💥',
+            Self::Signal { .. } => '☠',
+            Self::OutOfMemory => '🧠',
+            Self::UndefinedBehaviour => '🐉',
+            Self::Timeout { .. } => '⏰',
+            Self::SandboxFailure { .. } => '🔧',
+        }
+    }
+}
+
+impl fmt::Display for ExecutionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Success => write!(f, "Success"),
+            Self::CompileError => write!(f, "Compilation failed"),
+            Self::RuntimeError { exit_code } => write!(f, "Exited with code {}", exit_code),
+            Self::TestsFailed => write!(f, "Tests failed"),
+            Self::Panic => write!(f, "Panicked"),
+            Self::Signal { signal } => write!(f, "Killed by {}", signal_name(*signal)),
+            Self::OutOfMemory => write!(f, "Ran out of memory"),
+            Self::UndefinedBehaviour => write!(f, "Undefined behaviour"),
+            Self::Timeout { phase: None } => write!(f, "Took too long"),
+            Self::Timeout {
+                phase: Some(Phase::Compile),
+            } => write!(f, "Took too long to compile"),
+            Self::Timeout {
+                phase: Some(Phase::Run),
+            } => write!(f, "Took too long to run"),
+            Self::SandboxFailure { .. } => write!(f, "Could not run your code"),
+        }
+    }
+}
+
+/// Name of the common signals a program can die from
+fn signal_name(signal: i32) -> String {
+    match signal {
+        4 => String::from("SIGILL"),
+        6 => String::from("SIGABRT"),
+        7 => String::from("SIGBUS"),
+        8 => String::from("SIGFPE"),
+        9 => String::from("SIGKILL"),
+        11 => String::from("SIGSEGV"),
+        15 => String::from("SIGTERM"),
+        _ => format!("signal {}", signal),
+    }
+}
@@ -0,0 +1,138 @@
+use std::io::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::configuration;
+use crate::model::configurable::ConfigurableValue;
+use crate::model::container::ContainerSettings;
+use crate::model::sandbox::output::SandboxOutput;
+use crate::model::sandbox::SandboxBackend;
+
+/// When the pool replaces containers that were taken by a job
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefillPolicy {
+    /// Start a replacement as soon as a container is taken
+    Immediate,
+    /// Refill the whole pool once the last idle container is taken
+    WhenEmpty,
+}
+
+impl RefillPolicy {
+    /// Gets the refill policy from the configuration, defaulting to Immediate
+    fn from_config() -> Self {
+        match configuration::POOL_REFILL.value().as_str() {
+            "when-empty" => RefillPolicy::WhenEmpty,
+            _ => RefillPolicy::Immediate,
+        }
+    }
+}
+
+/// A pool of pre-started runner containers
+///
+/// Each container takes exactly one job and is then destroyed, so nothing a
+/// job leaves behind can be seen by the next one. When no idle container is
+/// available (or the job needs different settings) a fresh container is
+/// started for the job like before.
+pub struct ExecutorPool {
+    backend: Arc<dyn SandboxBackend>,
+    settings: ContainerSettings,
+    size: usize,
+    refill: RefillPolicy,
+    idle: Mutex<Vec<String>>,
+    starting: AtomicUsize,
+}
+
+impl ExecutorPool {
+    /// Creates an empty pool sized from the configuration, call `fill` to
+    /// start its containers. Backends that can't keep idle containers get no
+    /// pool at all
+    pub fn new(backend: Arc<dyn SandboxBackend>, settings: ContainerSettings) -> Arc<Self> {
+        let size = if backend.supports_idle() {
+            configuration::POOL_SIZE.value() as usize
+        } else {
+            0
+        };
+
+        Arc::new(ExecutorPool {
+            backend,
+            settings,
+            size,
+            refill: RefillPolicy::from_config(),
+            idle: Mutex::new(Vec::new()),
+            starting: AtomicUsize::new(0),
+        })
+    }
+
+    /// Settings the pool containers are started with
+    pub fn settings(&self) -> &ContainerSettings {
+        &self.settings
+    }
+
+    /// Starts containers in the background until the pool is full
+    pub fn fill(self: &Arc<Self>) {
+        let idle = self.idle.lock().unwrap().len();
+        let starting = self.starting.load(Ordering::SeqCst);
+        let missing = self.size.saturating_sub(idle + starting);
+
+        for _ in 0..missing {
+            self.starting.fetch_add(1, Ordering::SeqCst);
+            let pool = Arc::clone(self);
+
+            tokio::spawn(async move {
+                match pool.backend.start_idle(&pool.settings).await {
+                    Ok(id) => pool.idle.lock().unwrap().push(id),
+                    Err(e) => println!("Error starting pool container: {:?}", e),
+                }
+                pool.starting.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    }
+
+    /// Runs `command` in a pool container if one is ready, otherwise in a
+    /// freshly started one
+    pub async fn execute(
+        self: &Arc<Self>,
+        settings: &ContainerSettings,
+        command: &[String],
+    ) -> Result<SandboxOutput, Error> {
+        let container = if *settings == self.settings {
+            self.take()
+        } else {
+            None
+        };
+
+        match container {
+            Some(id) => {
+                let result = self.backend.execute_in(&id, settings, command).await;
+                self.discard(id);
+                result
+            }
+            None => self.backend.execute(settings, command).await,
+        }
+    }
+
+    /// Takes an idle container out of the pool and refills it per the policy
+    fn take(self: &Arc<Self>) -> Option<String> {
+        let (id, remaining) = {
+            let mut idle = self.idle.lock().unwrap();
+            (idle.pop(), idle.len())
+        };
+
+        if id.is_some() && (self.refill == RefillPolicy::Immediate || remaining == 0) {
+            self.fill();
+        }
+
+        id
+    }
+
+    /// Removes a used container in the background
+    fn discard(&self, id: String) {
+        let backend = Arc::clone(&self.backend);
+
+        tokio::spawn(async move {
+            if let Err(e) = backend.remove(&id).await {
+                println!("Error removing pool container {}: {:?}", id, e);
+            }
+        });
+    }
+}
@@ -0,0 +1,171 @@
+use serenity::model::id::UserId;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+/// Position reported to a job once it is allowed to start
+pub const STARTED: usize = 0;
+
+/// A job waiting for its turn
+struct Waiting {
+    id: u64,
+    position: watch::Sender<usize>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// Number of jobs currently holding a slot
+    running: usize,
+    /// Waiting jobs of every user, in the order they were queued
+    waiting: HashMap<UserId, VecDeque<Waiting>>,
+    /// Users with waiting jobs, in the order they get to start their next one
+    rotation: VecDeque<UserId>,
+    /// ID handed to the next job
+    next_id: u64,
+}
+
+/// Limits how many jobs run at once, taking turns between users
+///
+/// Jobs are started round-robin per user, so someone queueing ten runs only
+/// delays everyone else by one run at a time.
+pub struct JobQueue {
+    max_parallel: usize,
+    state: Mutex<QueueState>,
+}
+
+impl JobQueue {
+    pub fn new(max_parallel: usize) -> Arc<Self> {
+        Arc::new(JobQueue {
+            max_parallel: max_parallel.max(1),
+            state: Mutex::new(QueueState::default()),
+        })
+    }
+
+    /// Queues a job for `user`, the returned ticket holds its slot once it
+    /// started and gives it back when dropped
+    pub fn join(self: &Arc<Self>, user: UserId) -> Ticket {
+        let mut state = self.state.lock().unwrap();
+
+        let id = state.next_id;
+        state.next_id += 1;
+
+        let (sender, receiver) = watch::channel(usize::MAX);
+        state.waiting.entry(user).or_default().push_back(Waiting {
+            id,
+            position: sender,
+        });
+        if !state.rotation.contains(&user) {
+            state.rotation.push_back(user);
+        }
+
+        self.dispatch(&mut state);
+
+        Ticket {
+            queue: Arc::clone(self),
+            id,
+            user,
+            position: receiver,
+        }
+    }
+
+    /// Starts as many waiting jobs as there are free slots, then tells the
+    /// rest where they are in the queue
+    fn dispatch(&self, state: &mut QueueState) {
+        while state.running < self.max_parallel {
+            let user = match state.rotation.pop_front() {
+                Some(user) => user,
+                None => break,
+            };
+
+            let jobs = state.waiting.get_mut(&user).unwrap();
+            let job = jobs.pop_front().unwrap();
+            if jobs.is_empty() {
+                state.waiting.remove(&user);
+            } else {
+                state.rotation.push_back(user);
+            }
+
+            // A job whose ticket is already gone doesn't need its slot
+            if job.position.send(STARTED).is_ok() {
+                state.running += 1;
+            }
+        }
+
+        // Walk the queue in the order jobs will start: the first job of every
+        // user in rotation order, then the second job of each, and so on
+        let mut position = 1;
+        let mut round = 0;
+        loop {
+            let mut any = false;
+            for user in &state.rotation {
+                if let Some(job) = state.waiting[user].get(round) {
+                    // Every change wakes the job up to tell the user, so only
+                    // send the ones that are news
+                    if *job.position.borrow() != position {
+                        job.position.send(position).ok();
+                    }
+                    position += 1;
+                    any = true;
+                }
+            }
+            if !any {
+                break;
+            }
+            round += 1;
+        }
+    }
+
+    /// Removes a job that gave up waiting, or frees the slot of one that ran
+    fn leave(&self, user: UserId, id: u64) {
+        let mut state = self.state.lock().unwrap();
+
+        let was_waiting = match state.waiting.get_mut(&user) {
+            Some(jobs) => {
+                let before = jobs.len();
+                jobs.retain(|job| job.id != id);
+                let removed = jobs.len() != before;
+
+                if jobs.is_empty() {
+                    state.waiting.remove(&user);
+                    state.rotation.retain(|u| *u != user);
+                }
+                removed
+            }
+            None => false,
+        };
+
+        if !was_waiting {
+            state.running -= 1;
+        }
+
+        self.dispatch(&mut state);
+    }
+}
+
+/// A place in the job queue
+pub struct Ticket {
+    queue: Arc<JobQueue>,
+    id: u64,
+    user: UserId,
+    position: watch::Receiver<usize>,
+}
+
+impl Ticket {
+    /// Current position in the queue, STARTED once the job may run
+    pub fn position(&self) -> usize {
+        *self.position.borrow()
+    }
+
+    /// Waits for the position to change and returns the new one
+    pub async fn changed(&mut self) -> usize {
+        // The sender lives in the queue for as long as we're waiting in it
+        self.position.changed().await.ok();
+        self.position()
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        self.queue.leave(self.user, self.id);
+    }
+}
@@ -1,67 +1,89 @@
 use async_trait::async_trait;
-use process_control::{ChildExt, Control, Output};
 use std::io;
-use std::io::Error;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::model::container::{get_container_settings, ContainerActions, ContainerSettings};
+use crate::model::container::ContainerSettings;
+use crate::model::options::{Mode, RunOptions};
+use crate::model::outcome::ExecutionOutcome;
+use crate::model::pool::ExecutorPool;
+use crate::model::snippet::Program;
+use crate::model::trampoline::{Payload, Task};
 
 #[async_trait]
 pub trait Runnable {
-    async fn run(&self) -> Result<Output, Error>;
+    async fn run(
+        &self,
+        pool: &Arc<ExecutorPool>,
+        options: &RunOptions,
+        task: Task,
+    ) -> ExecutionOutcome;
     async fn run_with_settings(
         &self,
+        pool: &Arc<ExecutorPool>,
         container_settings: ContainerSettings,
-    ) -> Result<Output, Error>;
+        options: &RunOptions,
+        task: Task,
+    ) -> ExecutionOutcome;
 }
 
 #[async_trait]
 impl Runnable for String {
-    async fn run(&self) -> Result<Output, Error> {
-        let settings = get_container_settings();
-        self.run_with_settings(settings).await
+    async fn run(
+        &self,
+        pool: &Arc<ExecutorPool>,
+        options: &RunOptions,
+        task: Task,
+    ) -> ExecutionOutcome {
+        // Miri is slow, so it gets its own limits as well as its own image
+        let settings = match task {
+            Task::Run(Mode::Miri) => pool.settings().for_miri(),
+            _ => options.toolchain.settings(pool.settings()),
+        };
+
+        match settings {
+            Some(settings) => {
+                // Snippets without a main get one, except for tests which
+                // can't live inside of it
+                let program = match task {
+                    Task::Run(mode) if mode != Mode::Test => Program::new(self),
+                    _ => Program::unchanged(self),
+                };
+                let mut outcome = program
+                    .source
+                    .run_with_settings(pool, settings, options, task)
+                    .await;
+                outcome.stderr = program.map_output(&outcome.stderr);
+                if task == Task::Run(Mode::Miri) {
+                    outcome.classify_miri();
+                }
+                outcome
+            }
+            None => ExecutionOutcome::from_result(
+                Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    options.unavailable().unwrap_or_default(),
+                )),
+                Duration::ZERO,
+            ),
+        }
     }
 
     async fn run_with_settings(
         &self,
+        pool: &Arc<ExecutorPool>,
         container_settings: ContainerSettings,
-    ) -> Result<Output, Error> {
+        options: &RunOptions,
+        task: Task,
+    ) -> ExecutionOutcome {
         // TODO: the original rustbot had support for running with arguments, may be worth adding this in the future
         // https://github.com/TheConner/RustBot/blob/main/src/commands/run.rs#L37-L41
 
-        // In order to run an arbitrary string with the current design, we have to first base64 the content
-        // and then run the command with the base64'd content as stdin.
-        let encoded_program = base64::encode(&self);
-
-        // Next, we have to build the command that invokes the trampoline inside the container
-        let container_command = format!("trampoline {}", encoded_program);
-
-        //println!("{}", container_command);
-
-        // Because std::command does not give me the ability to override / modify
-        // how arguments are escaped I have to do some stupid hack to make this
-        // work. For example, if I wanted to run
-        // podman run rustbot:latest ls -al
-        // this would be impossible if I did
-        //
-        //  std::process::Command::new("podman")
-        //    .args(["run", "rustbot:latest", "ls -al"])
-        //    .output()
-        //    .expect("failed to invoke container");
-        //
-        // As the ls -al would be quoted, and the container would try to execute
-        // `ls -al` which would fail. The alternative is to seperate "ls", "-al"
-        // which would also fail as the container would run `ls` then `-al`
-        // ... what a stupid design
-        // So instead of embracing the safety this API gives you, i'm just invoking
-        // a shell with a payload I deem as safe
-        let process = container_settings.invoke_command(container_command);
+        // The trampoline takes care of building and running the code inside the container
+        let container_command = Payload::new(self, &container_settings, options, task).command();
 
-        process?
-            .controlled_with_output()
-            .time_limit(Duration::from_millis(container_settings.max_runtime))
-            .terminate_for_timeout()
-            .wait()?
-            .ok_or_else(|| Error::new(io::ErrorKind::TimedOut, "Process timed out"))
+        let started = Instant::now();
+        let result = pool.execute(&container_settings, &container_command).await;
+        ExecutionOutcome::from_result(result, started.elapsed())
     }
 }
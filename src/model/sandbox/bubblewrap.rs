@@ -0,0 +1,155 @@
+use async_trait::async_trait;
+use std::io;
+use std::io::Error;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use tokio::process::Command;
+
+use crate::model::container::{parse_size, ContainerSettings};
+use crate::model::sandbox::output::SandboxOutput;
+use crate::model::sandbox::{log_run, run_with_time_limit, SandboxBackend};
+
+/// Environment of the rust runner images, which would otherwise come from the
+/// image's config. Nothing of our own environment is passed in, it has the
+/// bot token in it
+const ENVIRONMENT: [(&str, &str); 4] = [
+    (
+        "PATH",
+        "/usr/local/cargo/bin:/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin",
+    ),
+    ("HOME", "/tmp"),
+    ("CARGO_HOME", "/usr/local/cargo"),
+    ("RUSTUP_HOME", "/usr/local/rustup"),
+];
+
+/// Runs code with bubblewrap, for hosts without a container engine
+///
+/// Instead of images, this needs the filesystem of every runner image unpacked
+/// into a directory of its own under `rootfs`, see `image_rootfs`. As
+/// bubblewrap has no cgroup support of its own, every run is put in a
+/// transient systemd scope that enforces the resource limits.
+pub struct Bubblewrap {
+    rootfs: String,
+}
+
+impl Bubblewrap {
+    pub fn new(rootfs: String) -> Self {
+        Bubblewrap { rootfs }
+    }
+
+    /// Where the filesystem of `image` is unpacked, eg. the stable image
+    /// ghcr.io/theconner/rustbot-runner:latest goes in
+    /// <rootfs>/ghcr.io_theconner_rustbot-runner_latest
+    fn image_rootfs(&self, image: &str) -> String {
+        format!("{}/{}", self.rootfs, image.replace(['/', ':', '@'], "_"))
+    }
+
+    /// Turns a ContainerSettings instance into systemd-run args for a scope
+    /// with the same limits a container would get
+    fn scope_flags(settings: &ContainerSettings) -> Vec<String> {
+        let mut flags = vec![String::from("--scope"), String::from("--quiet")];
+
+        // Users other than root manage their scopes with their own systemd
+        let is_root = std::fs::metadata("/proc/self").is_ok_and(|proc| proc.uid() == 0);
+        if !is_root {
+            flags.push(String::from("--user"));
+        }
+
+        flags.push(format!("--property=TasksMax={}", settings.pid_limit));
+        flags.push(format!(
+            "--property=CPUQuota={}%",
+            (settings.cpus() * 100.0).round() as u64
+        ));
+        if let Some(memory) = parse_size(&settings.memory) {
+            flags.push(format!("--property=MemoryMax={}", memory));
+        }
+        if let Some(swap) = parse_size(&settings.swap) {
+            flags.push(format!("--property=MemorySwapMax={}", swap));
+        }
+
+        flags
+    }
+
+    /// Turns a ContainerSettings instance into bwrap args
+    fn sandbox_flags(&self, settings: &ContainerSettings) -> Vec<String> {
+        let rootfs = self.image_rootfs(&settings.image);
+        let mut flags: Vec<String> = [
+            "--ro-bind",
+            &rootfs,
+            "/",
+            "--dev",
+            "/dev",
+            "--proc",
+            "/proc",
+            "--tmpfs",
+            "/tmp",
+            "--clearenv",
+            "--unshare-all",
+            "--cap-drop",
+            "ALL",
+            "--die-with-parent",
+            "--new-session",
+        ]
+        .iter()
+        .map(|flag| flag.to_string())
+        .collect();
+
+        for (name, value) in ENVIRONMENT {
+            flags.extend(["--setenv", name, value].map(String::from));
+        }
+
+        if settings.network != "none" {
+            flags.push(String::from("--share-net"));
+        }
+
+        flags
+    }
+}
+
+#[async_trait]
+impl SandboxBackend for Bubblewrap {
+    fn name(&self) -> &'static str {
+        "bubblewrap"
+    }
+
+    /// There is nothing to pull, but the image's root filesystem has to exist
+    async fn pull_image(&self, settings: &ContainerSettings) -> Result<(), Error> {
+        let rootfs = self.image_rootfs(&settings.image);
+        if Path::new(&rootfs).is_dir() {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "Sandbox root filesystem {} for {} does not exist, unpack the image there \
+                    or leave its CONTAINER_IMAGE_* item empty",
+                    rootfs, settings.image
+                ),
+            ))
+        }
+    }
+
+    async fn execute(
+        &self,
+        settings: &ContainerSettings,
+        command: &[String],
+    ) -> Result<SandboxOutput, Error> {
+        let mut invocation = Command::new("systemd-run");
+        invocation
+            .args(Self::scope_flags(settings))
+            .arg("--")
+            .arg("bwrap")
+            .args(self.sandbox_flags(settings))
+            .arg("--")
+            .args(command);
+
+        log_run(
+            "systemd-run",
+            "bwrap",
+            &self.image_rootfs(&settings.image),
+            command,
+        );
+
+        run_with_time_limit(invocation, settings).await
+    }
+}
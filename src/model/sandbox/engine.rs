@@ -0,0 +1,183 @@
+use async_trait::async_trait;
+use std::io::Error;
+
+use crate::model::container::{parse_size, ContainerSettings};
+use crate::model::invocation::ContainerInvocation;
+use crate::model::sandbox::output::SandboxOutput;
+use crate::model::sandbox::{
+    check_status, container_id_from, container_ids_from, container_name, log_run,
+    run_with_time_limit, timed_out, SandboxBackend, CONTAINER_LABEL, IDLE_COMMAND,
+};
+
+/// Runs code in containers through the CLI of a container engine, podman and
+/// docker take the same commands save for a few flags
+pub struct CliEngine {
+    name: &'static str,
+    program: &'static str,
+    /// How the engine spells turning off privilege escalation
+    no_new_privileges: &'static str,
+    /// Flags for removing a container, whether or not it's still there
+    remove_flags: &'static [&'static str],
+}
+
+impl CliEngine {
+    /// The podman CLI, or podman-remote with the socket passed into our own
+    /// container
+    pub fn podman(remote: bool) -> Self {
+        CliEngine {
+            name: "podman",
+            program: if remote { "podman-remote" } else { "podman" },
+            no_new_privileges: "--security-opt=no-new-privileges",
+            // --ignore as --rm containers may already have removed themselves
+            remove_flags: &["--force", "--ignore"],
+        }
+    }
+
+    /// The docker CLI
+    pub fn docker() -> Self {
+        CliEngine {
+            name: "docker",
+            program: "docker",
+            no_new_privileges: "--security-opt=no-new-privileges:true",
+            remove_flags: &["--force"],
+        }
+    }
+
+    /// Name and label that let us find the container again
+    fn identity_flags(name: &str) -> Vec<String> {
+        vec![
+            format!("--name={}", name),
+            format!("--label={}=true", CONTAINER_LABEL),
+        ]
+    }
+
+    /// Sends SIGKILL to a container, failures are only logged as the
+    /// container may already be gone
+    async fn kill(&self, name: &str) {
+        let command = ContainerInvocation::new(self.program, "kill")
+            .container(name)
+            .build();
+
+        if let Err(e) = check_status(command, "kill container").await {
+            println!("Error killing container {}: {:?}", name, e);
+        }
+    }
+
+    /// Turns a ContainerSettings instance into CLI args for the engine
+    fn runtime_flags(&self, settings: &ContainerSettings) -> Vec<String> {
+        let mut flags = vec![
+            String::from("--cap-drop=ALL"),
+            String::from(self.no_new_privileges),
+            format!("--cpus={}", settings.cpu),
+            format!("--memory={}", settings.memory),
+            format!("--network={}", settings.network),
+            format!("--pids-limit={}", settings.pid_limit),
+        ];
+
+        // Both engines take memory + swap as a single combined limit
+        if let (Some(memory), Some(swap)) =
+            (parse_size(&settings.memory), parse_size(&settings.swap))
+        {
+            flags.push(format!("--memory-swap={}", memory + swap));
+        }
+
+        flags
+    }
+}
+
+#[async_trait]
+impl SandboxBackend for CliEngine {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn supports_idle(&self) -> bool {
+        true
+    }
+
+    async fn pull_image(&self, settings: &ContainerSettings) -> Result<(), Error> {
+        let command = ContainerInvocation::new(self.program, "pull")
+            .image(&settings.image)
+            .build();
+
+        check_status(command, "pull container image").await
+    }
+
+    async fn execute(
+        &self,
+        settings: &ContainerSettings,
+        command: &[String],
+    ) -> Result<SandboxOutput, Error> {
+        let name = container_name();
+        let invocation = ContainerInvocation::new(self.program, "run")
+            .flag("--rm")
+            .flags(Self::identity_flags(&name))
+            .flags(self.runtime_flags(settings))
+            .image(&settings.image)
+            .command(command);
+
+        log_run(self.program, "run", &name, command);
+
+        let result = run_with_time_limit(invocation.build(), settings).await;
+
+        // Killing the CLI client leaves the container itself running
+        if timed_out(&result) {
+            self.kill(&name).await;
+            if let Err(e) = self.remove(&name).await {
+                println!("Error removing container {}: {:?}", name, e);
+            }
+        }
+
+        result
+    }
+
+    async fn start_idle(&self, settings: &ContainerSettings) -> Result<String, Error> {
+        let command = ContainerInvocation::new(self.program, "run")
+            .flag("--detach")
+            .flags(Self::identity_flags(&container_name()))
+            .flags(self.runtime_flags(settings))
+            .image(&settings.image)
+            .command(IDLE_COMMAND)
+            .build();
+
+        container_id_from(command, "start idle container").await
+    }
+
+    async fn execute_in(
+        &self,
+        id: &str,
+        settings: &ContainerSettings,
+        command: &[String],
+    ) -> Result<SandboxOutput, Error> {
+        let invocation = ContainerInvocation::new(self.program, "exec")
+            .container(id)
+            .command(command);
+
+        log_run(self.program, "exec", id, command);
+
+        run_with_time_limit(invocation.build(), settings).await
+    }
+
+    async fn remove(&self, id: &str) -> Result<(), Error> {
+        let command = ContainerInvocation::new(self.program, "rm")
+            .flags(self.remove_flags.iter().copied())
+            .container(id)
+            .build();
+
+        check_status(command, "remove container").await
+    }
+
+    async fn reap(&self) -> Result<usize, Error> {
+        let command = ContainerInvocation::new(self.program, "ps")
+            .flags(["--all", "--quiet"])
+            .flag(format!("--filter=label={}", CONTAINER_LABEL))
+            .build();
+        let ids = container_ids_from(command, "list leftover containers").await?;
+
+        for id in &ids {
+            self.remove(id).await?;
+        }
+
+        Ok(ids.len())
+    }
+}
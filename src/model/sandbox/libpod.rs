@@ -0,0 +1,625 @@
+use hyper::body::{Bytes, HttpBody};
+use hyper::{Body, Client, Method, Request, StatusCode};
+use hyperlocal::{UnixClientExt, UnixConnector};
+use serde::Deserialize;
+use serde_json::Value;
+use std::io;
+use std::io::Error;
+
+use crate::model::sandbox::output::{OutputCollector, Stream};
+
+/// Version of the libpod API we talk to, podman 4 is the minimum
+const API_VERSION: &str = "v4.0.0";
+
+/// A minimal async client for the libpod REST API, over podman's unix socket
+pub struct LibpodClient {
+    socket: String,
+    client: Client<UnixConnector>,
+}
+
+/// Error body returned by libpod for any non-2xx response
+#[derive(Deserialize)]
+struct ApiError {
+    message: String,
+}
+
+/// Response body of a container create, also the bits of a container list
+/// entry we care about
+#[derive(Deserialize)]
+struct CreateResponse {
+    #[serde(rename = "Id")]
+    id: String,
+}
+
+impl LibpodClient {
+    /// Connects to the socket in `container_host`, which uses the same
+    /// unix:/path/to/podman.sock format as podman's CONTAINER_HOST
+    pub fn new(container_host: &str) -> Self {
+        let socket = container_host
+            .trim_start_matches("unix://")
+            .trim_start_matches("unix:")
+            .to_string();
+
+        LibpodClient {
+            socket,
+            client: Client::unix(),
+        }
+    }
+
+    /// Sends a request and returns the response once its headers arrived,
+    /// turning libpod errors into io errors
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<hyper::Response<Body>, Error> {
+        let uri: hyper::Uri =
+            hyperlocal::Uri::new(&self.socket, &format!("/{}/libpod{}", API_VERSION, path)).into();
+
+        let request = match body {
+            Some(body) => Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Content-Type", "application/json")
+                .body(Body::from(body.to_string())),
+            None => Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty()),
+        }
+        .map_err(io::Error::other)?;
+
+        let response = self
+            .client
+            .request(request)
+            .await
+            .map_err(io::Error::other)?;
+
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            let status = response.status();
+            let body = read_body(response.into_body()).await?;
+            Err(api_error(status, &body))
+        }
+    }
+
+    /// Like `send`, but also waits for the whole response body
+    async fn call(&self, method: Method, path: &str, body: Option<Value>) -> Result<Bytes, Error> {
+        let response = self.send(method, path, body).await?;
+        read_body(response.into_body()).await
+    }
+
+    /// Pulls an image from a registry
+    pub async fn pull(&self, image: &str) -> Result<(), Error> {
+        let body = self
+            .call(
+                Method::POST,
+                &format!("/images/pull?quiet=true&reference={}", encode(image)),
+                None,
+            )
+            .await?;
+
+        // Pull progress is streamed as one JSON object per line, failures are
+        // only reported in there as the status code is sent up front
+        for line in body.split(|b| *b == b'\n').filter(|line| !line.is_empty()) {
+            let progress: Value = serde_json::from_slice(line)?;
+            if let Some(error) = progress.get("error").and_then(Value::as_str) {
+                if !error.is_empty() {
+                    return Err(io::Error::other(format!(
+                        "Could not pull container image, {}",
+                        error
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Creates a container from a libpod SpecGenerator and returns its ID
+    pub async fn create(&self, spec: Value) -> Result<String, Error> {
+        let body = self
+            .call(Method::POST, "/containers/create", Some(spec))
+            .await?;
+        let created: CreateResponse = serde_json::from_slice(&body)?;
+        Ok(created.id)
+    }
+
+    /// Lists the IDs of all containers with `label`, running or not
+    pub async fn list(&self, label: &str) -> Result<Vec<String>, Error> {
+        let filters = serde_json::json!({ "label": [label] }).to_string();
+        let body = self
+            .call(
+                Method::GET,
+                &format!("/containers/json?all=true&filters={}", encode(&filters)),
+                None,
+            )
+            .await?;
+        let containers: Vec<CreateResponse> = serde_json::from_slice(&body)?;
+        Ok(containers.into_iter().map(|c| c.id).collect())
+    }
+
+    /// Starts a created container
+    pub async fn start(&self, id: &str) -> Result<(), Error> {
+        self.call(Method::POST, &format!("/containers/{}/start", id), None)
+            .await
+            .map(|_| ())
+    }
+
+    /// Attaches to the output of a container
+    ///
+    /// This returns as soon as the attach is set up, the body then streams the
+    /// multiplexed stdout and stderr until the container exits. Attach before
+    /// starting the container so that no output is lost.
+    pub async fn attach(&self, id: &str) -> Result<Body, Error> {
+        let response = self
+            .send(
+                Method::POST,
+                &format!(
+                    "/containers/{}/attach?stream=true&stdout=true&stderr=true",
+                    id
+                ),
+                None,
+            )
+            .await?;
+        Ok(response.into_body())
+    }
+
+    /// Waits for a container to stop and returns its exit code
+    pub async fn wait(&self, id: &str) -> Result<i32, Error> {
+        let body = self
+            .call(
+                Method::POST,
+                &format!("/containers/{}/wait?condition=stopped", id),
+                None,
+            )
+            .await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Sends SIGKILL to a container
+    pub async fn kill(&self, id: &str) -> Result<(), Error> {
+        self.call(
+            Method::POST,
+            &format!("/containers/{}/kill?signal=SIGKILL", id),
+            None,
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Removes a container, killing it first if it is still running
+    pub async fn remove(&self, id: &str) -> Result<(), Error> {
+        self.call(
+            Method::DELETE,
+            &format!("/containers/{}?force=true", id),
+            None,
+        )
+        .await
+        .map(|_| ())
+    }
+}
+
+/// Response body of an exec inspect
+#[derive(Deserialize)]
+struct ExecInspect {
+    #[serde(rename = "ExitCode")]
+    exit_code: i32,
+}
+
+impl LibpodClient {
+    /// Creates an exec session running `command` in a running container and
+    /// returns the session ID
+    pub async fn exec_create(&self, id: &str, command: &[String]) -> Result<String, Error> {
+        let body = self
+            .call(
+                Method::POST,
+                &format!("/containers/{}/exec", id),
+                Some(serde_json::json!({
+                    "AttachStdout": true,
+                    "AttachStderr": true,
+                    "Cmd": command,
+                })),
+            )
+            .await?;
+        let created: CreateResponse = serde_json::from_slice(&body)?;
+        Ok(created.id)
+    }
+
+    /// Starts an exec session, the body streams its multiplexed stdout and
+    /// stderr until the command exits
+    pub async fn exec_start(&self, exec_id: &str) -> Result<Body, Error> {
+        let response = self
+            .send(
+                Method::POST,
+                &format!("/exec/{}/start", exec_id),
+                Some(serde_json::json!({ "Detach": false })),
+            )
+            .await?;
+        Ok(response.into_body())
+    }
+
+    /// Gets the exit code of a finished exec session
+    pub async fn exec_exit_code(&self, exec_id: &str) -> Result<i32, Error> {
+        let body = self
+            .call(Method::GET, &format!("/exec/{}/json", exec_id), None)
+            .await?;
+        let inspect: ExecInspect = serde_json::from_slice(&body)?;
+        Ok(inspect.exit_code)
+    }
+}
+
+/// Size of the header in front of every frame of an attach stream
+const FRAME_HEADER_SIZE: usize = 8;
+
+/// Splits an attach stream into stdout and stderr as it comes in, frames can
+/// be spread over any number of chunks
+///
+/// Every frame starts with an 8 byte header: the stream (1 = stdout,
+/// 2 = stderr), three bytes of padding and a big endian u32 length.
+pub struct Demultiplexer {
+    /// The part of the next frame's header we've seen so far
+    header: Vec<u8>,
+    /// Stream of the current frame
+    stream: Stream,
+    /// How much of the current frame's payload is still to come
+    remaining: usize,
+}
+
+impl Demultiplexer {
+    pub fn new() -> Self {
+        Demultiplexer {
+            header: Vec::with_capacity(FRAME_HEADER_SIZE),
+            stream: Stream::Stdout,
+            remaining: 0,
+        }
+    }
+
+    /// Hands the payload in `chunk` to `output`, by the stream it's on
+    pub fn demultiplex(&mut self, mut chunk: &[u8], output: &mut OutputCollector) {
+        while !chunk.is_empty() {
+            if self.remaining > 0 {
+                let (payload, rest) = chunk.split_at(self.remaining.min(chunk.len()));
+                output.push(self.stream, payload);
+                self.remaining -= payload.len();
+                chunk = rest;
+                continue;
+            }
+
+            let needed = FRAME_HEADER_SIZE - self.header.len();
+            let (header, rest) = chunk.split_at(needed.min(chunk.len()));
+            self.header.extend_from_slice(header);
+            chunk = rest;
+
+            if self.header.len() == FRAME_HEADER_SIZE {
+                self.stream = match self.header[0] {
+                    2 => Stream::Stderr,
+                    _ => Stream::Stdout,
+                };
+                let length = [
+                    self.header[4],
+                    self.header[5],
+                    self.header[6],
+                    self.header[7],
+                ];
+                self.remaining = u32::from_be_bytes(length) as usize;
+                self.header.clear();
+            }
+        }
+    }
+}
+
+/// Reads an attach stream until it ends, keeping only as much output as the
+/// collector allows
+pub async fn read_output(mut body: Body) -> Result<OutputCollector, Error> {
+    let mut demultiplexer = Demultiplexer::new();
+    let mut output = OutputCollector::new();
+
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(io::Error::other)?;
+        demultiplexer.demultiplex(&chunk, &mut output);
+    }
+
+    Ok(output)
+}
+
+/// Reads a whole response body
+pub async fn read_body(body: Body) -> Result<Bytes, Error> {
+    hyper::body::to_bytes(body).await.map_err(io::Error::other)
+}
+
+/// Turns a libpod error response into an io error
+fn api_error(status: StatusCode, body: &[u8]) -> Error {
+    let message = serde_json::from_slice::<ApiError>(body)
+        .map(|e| e.message)
+        .unwrap_or_else(|_| String::from_utf8_lossy(body).into_owned());
+
+    let kind = match status {
+        StatusCode::NOT_FOUND => io::ErrorKind::NotFound,
+        _ => io::ErrorKind::Other,
+    };
+
+    Error::new(kind, format!("libpod returned {}: {}", status, message))
+}
+
+/// Percent-encodes a query parameter value
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::ExitStatus;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{UnixListener, UnixStream};
+    use tokio::task::JoinHandle;
+
+    /// A response the fake libpod sends back: status code and body
+    type Canned = (u16, Vec<u8>);
+
+    /// A request the fake libpod got: method, path and body
+    type Received = (String, String, String);
+
+    /// A fake libpod that answers requests with `responses` in order, on a
+    /// socket of its own. Its task ends with the requests it got
+    fn fake_libpod(responses: Vec<Canned>) -> (String, JoinHandle<Vec<Received>>) {
+        static SOCKETS: AtomicUsize = AtomicUsize::new(0);
+        let socket = std::env::temp_dir().join(format!(
+            "ferris-bot-libpod-{}-{}.sock",
+            std::process::id(),
+            SOCKETS.fetch_add(1, Ordering::SeqCst)
+        ));
+        std::fs::remove_file(&socket).ok();
+        let listener = UnixListener::bind(&socket).unwrap();
+        let host = format!("unix:{}", socket.display());
+
+        let server = tokio::spawn(async move {
+            let mut received = Vec::new();
+            let mut connection: Option<UnixStream> = None;
+
+            for (status, body) in responses {
+                // hyper reuses its connection, unless it decides to open a new one
+                let request = loop {
+                    if let Some(stream) = &mut connection {
+                        if let Some(request) = read_request(stream).await {
+                            break request;
+                        }
+                    }
+                    connection = Some(listener.accept().await.unwrap().0);
+                };
+                received.push(request);
+
+                let head = format!(
+                    "HTTP/1.1 {} Canned\r\nContent-Length: {}\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                let stream = connection.as_mut().unwrap();
+                stream.write_all(head.as_bytes()).await.unwrap();
+                stream.write_all(&body).await.unwrap();
+            }
+
+            std::fs::remove_file(&socket).ok();
+            received
+        });
+
+        (host, server)
+    }
+
+    /// Reads one HTTP request off `connection`, None if it was closed
+    async fn read_request(connection: &mut UnixStream) -> Option<Received> {
+        let mut raw = Vec::new();
+        let mut byte = [0; 1];
+        while !raw.ends_with(b"\r\n\r\n") {
+            if connection.read(&mut byte).await.ok()? == 0 {
+                return None;
+            }
+            raw.push(byte[0]);
+        }
+
+        let head = String::from_utf8(raw).unwrap();
+        let mut request_line = head.lines().next().unwrap().split(' ');
+        let method = request_line.next().unwrap().to_string();
+        let path = request_line.next().unwrap().to_string();
+        let length = head
+            .lines()
+            .find_map(|line| {
+                let (name, value) = line.split_once(": ")?;
+                name.eq_ignore_ascii_case("content-length")
+                    .then(|| value.parse::<usize>().unwrap())
+            })
+            .unwrap_or(0);
+
+        let mut body = vec![0; length];
+        connection.read_exact(&mut body).await.ok()?;
+
+        Some((method, path, String::from_utf8(body).unwrap()))
+    }
+
+    /// An attach stream frame with `payload` on `stream` (1 = stdout, 2 = stderr)
+    fn frame(stream: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![stream, 0, 0, 0];
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    fn json(body: &str) -> Vec<u8> {
+        body.as_bytes().to_vec()
+    }
+
+    #[tokio::test]
+    async fn runs_a_container_from_create_to_remove() {
+        let mut attached = frame(1, b"hello ");
+        attached.extend(frame(2, b"oops\n"));
+        attached.extend(frame(1, b"world\n"));
+
+        let (host, server) = fake_libpod(vec![
+            (201, json(r#"{"Id":"abc123","Warnings":[]}"#)),
+            (200, attached),
+            (204, Vec::new()),
+            (200, json("3")),
+            (200, json("[]")),
+        ]);
+        let client = LibpodClient::new(&host);
+
+        let id = client
+            .create(serde_json::json!({ "image": "runner" }))
+            .await
+            .unwrap();
+        assert_eq!(id, "abc123");
+
+        let body = client.attach(&id).await.unwrap();
+        let output = read_output(body)
+            .await
+            .unwrap()
+            .finish(ExitStatus::from_raw(0));
+        assert_eq!(output.stdout, b"hello world\n");
+        assert_eq!(output.stderr, b"oops\n");
+        assert!(!output.truncated);
+
+        client.start(&id).await.unwrap();
+        assert_eq!(client.wait(&id).await.unwrap(), 3);
+        client.remove(&id).await.unwrap();
+
+        let received = server.await.unwrap();
+        let requests: Vec<(&str, &str)> = received
+            .iter()
+            .map(|(method, path, _)| (method.as_str(), path.as_str()))
+            .collect();
+        assert_eq!(
+            requests,
+            [
+                ("POST", "/v4.0.0/libpod/containers/create"),
+                (
+                    "POST",
+                    "/v4.0.0/libpod/containers/abc123/attach?stream=true&stdout=true&stderr=true"
+                ),
+                ("POST", "/v4.0.0/libpod/containers/abc123/start"),
+                (
+                    "POST",
+                    "/v4.0.0/libpod/containers/abc123/wait?condition=stopped"
+                ),
+                ("DELETE", "/v4.0.0/libpod/containers/abc123?force=true"),
+            ]
+        );
+        assert_eq!(received[0].2, r#"{"image":"runner"}"#);
+    }
+
+    #[tokio::test]
+    async fn maps_error_bodies_to_io_errors() {
+        let (host, server) = fake_libpod(vec![
+            (
+                404,
+                json(
+                    r#"{"cause":"no such container","message":"no container with name or ID \"gone\" found","response":404}"#,
+                ),
+            ),
+            (500, json("something broke")),
+        ]);
+        let client = LibpodClient::new(&host);
+
+        let missing = client.start("gone").await.unwrap_err();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+        assert_eq!(
+            missing.to_string(),
+            "libpod returned 404 Not Found: no container with name or ID \"gone\" found"
+        );
+
+        // Bodies that aren't libpod's JSON errors are passed on as they are
+        let broken = client.remove("abc123").await.unwrap_err();
+        assert_eq!(broken.kind(), io::ErrorKind::Other);
+        assert_eq!(
+            broken.to_string(),
+            "libpod returned 500 Internal Server Error: something broke"
+        );
+
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reports_pull_errors_from_the_progress_stream() {
+        let (host, server) = fake_libpod(vec![
+            (
+                200,
+                json("{\"stream\":\"pulling\"}\n{\"id\":\"sha256:1\"}\n"),
+            ),
+            (200, json("{\"error\":\"manifest unknown\"}\n")),
+        ]);
+        let client = LibpodClient::new(&host);
+
+        client.pull("ghcr.io/runner:latest").await.unwrap();
+        let failed = client.pull("ghcr.io/missing:latest").await.unwrap_err();
+        assert_eq!(
+            failed.to_string(),
+            "Could not pull container image, manifest unknown"
+        );
+
+        let received = server.await.unwrap();
+        assert_eq!(
+            received[0].1,
+            "/v4.0.0/libpod/images/pull?quiet=true&reference=ghcr.io%2Frunner%3Alatest"
+        );
+    }
+
+    #[test]
+    fn demultiplexes_frames_split_across_chunks() {
+        let mut raw = frame(1, b"out");
+        raw.extend(frame(2, b"err"));
+        raw.extend(frame(1, b""));
+        raw.extend(frame(1, b"put"));
+
+        // Feed it a byte at a time, the worst a stream can do
+        let mut demultiplexer = Demultiplexer::new();
+        let mut output = OutputCollector::with_limit(1024);
+        for byte in raw.chunks(1) {
+            demultiplexer.demultiplex(byte, &mut output);
+        }
+        let output = output.finish(ExitStatus::from_raw(0));
+
+        assert_eq!(output.stdout, b"output");
+        assert_eq!(output.stderr, b"err");
+    }
+
+    #[test]
+    fn demultiplexing_stops_keeping_output_at_the_limit() {
+        let mut raw = frame(1, b"0123456789");
+        raw.extend(frame(2, b"spam\nferris-bot: timeout run\n"));
+
+        let mut output = OutputCollector::with_limit(8);
+        Demultiplexer::new().demultiplex(&raw, &mut output);
+        let output = output.finish(ExitStatus::from_raw(0));
+
+        assert_eq!(output.stdout, b"01234567");
+        // The trampoline's report survives the cut
+        assert_eq!(output.stderr, b"\nferris-bot: timeout run\n");
+        assert!(output.truncated);
+    }
+
+    #[test]
+    fn encodes_query_values() {
+        assert_eq!(encode("runner-1.0_x~"), "runner-1.0_x~");
+        assert_eq!(
+            encode("ghcr.io/theconner/rustbot-runner:latest"),
+            "ghcr.io%2Ftheconner%2Frustbot-runner%3Alatest"
+        );
+        assert_eq!(
+            encode(r#"{"label":["a=b"]}"#),
+            "%7B%22label%22%3A%5B%22a%3Db%22%5D%7D"
+        );
+        assert_eq!(encode("ü"), "%C3%BC");
+    }
+}
@@ -0,0 +1,224 @@
+use async_trait::async_trait;
+use std::io;
+use std::io::Error;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+
+use crate::configuration;
+use crate::model::configurable::ConfigurableValue;
+use crate::model::container::ContainerSettings;
+use crate::model::sandbox::output::{OutputCollector, SandboxOutput, Stream};
+
+pub mod bubblewrap;
+pub mod engine;
+pub mod libpod;
+pub mod output;
+pub mod podman_api;
+
+/// Something that can run untrusted code in isolation, eg. a container engine
+#[async_trait]
+pub trait SandboxBackend: Send + Sync {
+    /// Name of the backend, as used in the SANDBOX_BACKEND config item
+    fn name(&self) -> &'static str;
+
+    /// Makes sure everything needed to run `settings` is available locally
+    async fn pull_image(&self, settings: &ContainerSettings) -> Result<(), Error>;
+
+    /// Runs `command` inside the sandbox, each element being one argument
+    async fn execute(
+        &self,
+        settings: &ContainerSettings,
+        command: &[String],
+    ) -> Result<SandboxOutput, Error>;
+
+    /// Whether the backend implements `start_idle`, `execute_in` and `remove`
+    fn supports_idle(&self) -> bool {
+        false
+    }
+
+    /// Starts an idle container that a job can be executed in later and
+    /// returns its ID, this is what the warm pool is made of
+    async fn start_idle(&self, _settings: &ContainerSettings) -> Result<String, Error> {
+        Err(unsupported(self.name()))
+    }
+
+    /// Runs `command` inside a container started with `start_idle`
+    async fn execute_in(
+        &self,
+        _id: &str,
+        _settings: &ContainerSettings,
+        _command: &[String],
+    ) -> Result<SandboxOutput, Error> {
+        Err(unsupported(self.name()))
+    }
+
+    /// Kills and removes a container started with `start_idle`
+    async fn remove(&self, _id: &str) -> Result<(), Error> {
+        Err(unsupported(self.name()))
+    }
+
+    /// Removes every container we started that is still around, eg. after a
+    /// crash, and returns how many were removed
+    async fn reap(&self) -> Result<usize, Error> {
+        Ok(0)
+    }
+}
+
+/// Command that keeps an idle container alive until a job is executed in it
+pub const IDLE_COMMAND: [&str; 2] = ["sleep", "infinity"];
+
+/// Label put on every container we start, so leftovers can be found again
+pub const CONTAINER_LABEL: &str = "ferris-bot.managed";
+
+/// Gives every container a unique name, so it can be killed by name even when
+/// the engine never told us its ID
+pub fn container_name() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+
+    format!(
+        "ferris-bot-{}-{}",
+        started,
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+/// Gets the sandbox backend selected in the configuration
+pub fn get_sandbox_backend() -> Result<Arc<dyn SandboxBackend>, Error> {
+    let backend = configuration::SANDBOX_BACKEND.value();
+
+    match backend.as_str() {
+        "podman" => Ok(Arc::new(engine::CliEngine::podman(
+            configuration::IS_RUNNING_IN_CONTAINER.value(),
+        ))),
+        "podman-api" => Ok(Arc::new(podman_api::PodmanApi::new(
+            &configuration::CONTAINER_HOST.value(),
+        ))),
+        "docker" => Ok(Arc::new(engine::CliEngine::docker())),
+        "bubblewrap" => Ok(Arc::new(bubblewrap::Bubblewrap::new(
+            configuration::SANDBOX_ROOTFS.value(),
+        ))),
+        _ => Err(io::Error::other(format!(
+            "Unknown sandbox backend {}, expected one of podman, podman-api, docker, bubblewrap",
+            backend
+        ))),
+    }
+}
+
+/// Logs a run in `target` (a container, or where the sandbox lives) without
+/// the command itself, which is the whole trampoline and the program it runs
+fn log_run(program: &str, subcommand: &str, target: &str, command: &[String]) {
+    let task = command
+        .iter()
+        .find(|arg| arg.starts_with("mode="))
+        .map_or("", String::as_str);
+    println!("{} {} {} {}", program, subcommand, target, task);
+}
+
+/// Size of the chunks output is read in
+const CHUNK_SIZE: usize = 8192;
+
+/// Spawns a CLI based sandbox and waits for it, killing it once it exceeds
+/// its time limit
+///
+/// stdout and stderr are read while waiting, so a chatty program can't fill up
+/// a pipe and stall. Past the output limit they are still drained, but no
+/// longer kept
+async fn run_with_time_limit(
+    mut command: Command,
+    settings: &ContainerSettings,
+) -> Result<SandboxOutput, Error> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Dropping the wait below on timeout kills the process
+        .kill_on_drop(true)
+        .spawn()?;
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+
+    let run = async {
+        let mut output = OutputCollector::new();
+        let mut stdout_chunk = [0; CHUNK_SIZE];
+        let mut stderr_chunk = [0; CHUNK_SIZE];
+        let (mut stdout_done, mut stderr_done) = (false, false);
+
+        while !(stdout_done && stderr_done) {
+            tokio::select! {
+                read = stdout.read(&mut stdout_chunk), if !stdout_done => match read? {
+                    0 => stdout_done = true,
+                    n => output.push(Stream::Stdout, &stdout_chunk[..n]),
+                },
+                read = stderr.read(&mut stderr_chunk), if !stderr_done => match read? {
+                    0 => stderr_done = true,
+                    n => output.push(Stream::Stderr, &stderr_chunk[..n]),
+                },
+            }
+        }
+
+        let status = child.wait().await?;
+        Ok(output.finish(status))
+    };
+
+    match tokio::time::timeout(settings.time_limit(), run).await {
+        Ok(output) => output,
+        Err(_) => Err(Error::new(io::ErrorKind::TimedOut, "Process timed out")),
+    }
+}
+
+/// Waits for a pull (or similar setup) command and turns a non-zero exit into an error
+async fn check_status(mut command: Command, what: &str) -> Result<(), Error> {
+    let status = command.status().await?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "Could not {}, got error code {}",
+            what,
+            status.code().unwrap_or(-1)
+        )))
+    }
+}
+
+/// Runs a CLI command that prints a container ID, like `podman run -d`
+async fn container_id_from(mut command: Command, what: &str) -> Result<String, Error> {
+    let output = command.stderr(Stdio::inherit()).output().await?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(io::Error::other(format!(
+            "Could not {}, got error code {}",
+            what,
+            output.status.code().unwrap_or(-1)
+        )))
+    }
+}
+
+/// Runs a CLI command that prints one container ID per line, like `podman ps -q`
+async fn container_ids_from(command: Command, what: &str) -> Result<Vec<String>, Error> {
+    let output = container_id_from(command, what).await?;
+    Ok(output.lines().map(String::from).collect())
+}
+
+/// Was the run killed because it took too long?
+fn timed_out(result: &Result<SandboxOutput, Error>) -> bool {
+    matches!(result, Err(e) if e.kind() == io::ErrorKind::TimedOut)
+}
+
+/// Error for backends that can't keep idle containers around
+fn unsupported(backend: &str) -> Error {
+    Error::new(
+        io::ErrorKind::Unsupported,
+        format!("The {} backend does not support idle containers", backend),
+    )
+}
@@ -0,0 +1,104 @@
+use std::process::ExitStatus;
+
+use crate::configuration;
+use crate::model::configurable::ConfigurableValue;
+
+/// How much of the end of stderr is remembered past the limit, the
+/// trampoline reports on its last line
+const TAIL_LIMIT: usize = 4096;
+
+/// Which of the program's output streams some bytes came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// What a command left behind after running in the sandbox
+#[derive(Debug)]
+pub struct SandboxOutput {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// Whether there was more output than we keep
+    pub truncated: bool,
+}
+
+/// Collects the output of a command as it is read, keeping at most `limit`
+/// bytes of stdout and stderr together so a chatty program can't fill up our
+/// memory
+pub struct OutputCollector {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    limit: usize,
+    /// The last bytes of stderr that went past the limit
+    stderr_tail: Vec<u8>,
+    truncated: bool,
+}
+
+impl OutputCollector {
+    /// A collector keeping as much as the OUTPUT_MAX_BYTES config item allows
+    pub fn new() -> Self {
+        Self::with_limit(configuration::OUTPUT_MAX_BYTES.value() as usize)
+    }
+
+    pub fn with_limit(limit: usize) -> Self {
+        OutputCollector {
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            limit,
+            stderr_tail: Vec::new(),
+            truncated: false,
+        }
+    }
+
+    /// Keeps as much of `data` as still fits, dropping the rest
+    pub fn push(&mut self, stream: Stream, data: &[u8]) {
+        let kept = self.stdout.len() + self.stderr.len();
+        let fits = data.len().min(self.limit.saturating_sub(kept));
+        let (data, dropped) = data.split_at(fits);
+
+        match stream {
+            Stream::Stdout => self.stdout.extend_from_slice(data),
+            Stream::Stderr => self.stderr.extend_from_slice(data),
+        }
+
+        if dropped.is_empty() {
+            return;
+        }
+        self.truncated = true;
+        if stream == Stream::Stderr {
+            self.stderr_tail.extend_from_slice(dropped);
+            let excess = self.stderr_tail.len().saturating_sub(TAIL_LIMIT);
+            self.stderr_tail.drain(..excess);
+        }
+    }
+
+    pub fn finish(mut self, status: ExitStatus) -> SandboxOutput {
+        // Put the last line of stderr back, so the trampoline's report on
+        // how the run went isn't lost along with the rest
+        let tail = trim_newline(&self.stderr_tail);
+        let last_line = match tail.iter().rposition(|b| *b == b'\n') {
+            Some(start) => &tail[start + 1..],
+            None => tail,
+        };
+        if !last_line.is_empty() {
+            if !self.stderr.ends_with(b"\n") {
+                self.stderr.push(b'\n');
+            }
+            self.stderr.extend_from_slice(last_line);
+            self.stderr.push(b'\n');
+        }
+
+        SandboxOutput {
+            status,
+            stdout: self.stdout,
+            stderr: self.stderr,
+            truncated: self.truncated,
+        }
+    }
+}
+
+fn trim_newline(bytes: &[u8]) -> &[u8] {
+    bytes.strip_suffix(b"\n").unwrap_or(bytes)
+}
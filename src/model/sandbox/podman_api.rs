@@ -0,0 +1,184 @@
+use async_trait::async_trait;
+use serde_json::{json, Map, Value};
+use std::io;
+use std::io::Error;
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+
+use crate::model::container::{parse_size, ContainerSettings};
+use crate::model::sandbox::libpod::{read_output, LibpodClient};
+use crate::model::sandbox::output::SandboxOutput;
+use crate::model::sandbox::{container_name, SandboxBackend, CONTAINER_LABEL, IDLE_COMMAND};
+
+/// CFS period used to turn a CPU count into a quota, same as podman's default
+const CPU_PERIOD: u64 = 100_000;
+
+/// Runs code in podman containers through the libpod REST API, without
+/// starting a podman process per run
+pub struct PodmanApi {
+    client: LibpodClient,
+}
+
+impl PodmanApi {
+    pub fn new(container_host: &str) -> Self {
+        PodmanApi {
+            client: LibpodClient::new(container_host),
+        }
+    }
+
+    /// Turns a ContainerSettings instance into a libpod SpecGenerator
+    fn spec(settings: &ContainerSettings, command: &[String]) -> Value {
+        let mut spec = json!({
+            "name": container_name(),
+            "labels": { CONTAINER_LABEL: "true" },
+            "image": settings.image,
+            "command": command,
+            "cap_drop": ["ALL"],
+            "no_new_privileges": true,
+            "resource_limits": {
+                "pids": { "limit": settings.pid_limit },
+            },
+        });
+
+        if let Ok(cpu) = settings.cpu.parse::<f64>() {
+            spec["resource_limits"]["cpu"] = json!({
+                "quota": (cpu * CPU_PERIOD as f64) as i64,
+                "period": CPU_PERIOD,
+            });
+        }
+
+        if let Some(memory) = parse_size(&settings.memory) {
+            let mut limits = json!({ "limit": memory });
+            if let Some(swap) = parse_size(&settings.swap) {
+                limits["swap"] = json!(memory + swap);
+            }
+            spec["resource_limits"]["memory"] = limits;
+        }
+
+        match settings.network.as_str() {
+            "none" | "host" | "private" | "bridge" => {
+                spec["netns"] = json!({ "nsmode": settings.network });
+            }
+            network => {
+                // Anything else is the name of a podman network to join
+                let mut networks = Map::new();
+                networks.insert(network.to_string(), json!({}));
+                spec["netns"] = json!({ "nsmode": "bridge" });
+                spec["Networks"] = Value::Object(networks);
+            }
+        }
+
+        spec
+    }
+
+    /// Runs an already created container to completion and collects its output
+    async fn run_container(
+        &self,
+        id: &str,
+        settings: &ContainerSettings,
+    ) -> Result<SandboxOutput, Error> {
+        // Attach first so that nothing the program prints is missed
+        let attached = self.client.attach(id).await?;
+        let output = tokio::spawn(read_output(attached));
+
+        self.client.start(id).await?;
+
+        let wait = self.client.wait(id);
+        let exit_code = match tokio::time::timeout(settings.time_limit(), wait).await {
+            Ok(exit_code) => exit_code?,
+            Err(_) => {
+                self.client.kill(id).await?;
+                return Err(Error::new(io::ErrorKind::TimedOut, "Process timed out"));
+            }
+        };
+
+        let output = output.await.map_err(io::Error::other)??;
+        Ok(output.finish(exit_status(exit_code)))
+    }
+}
+
+/// Turns an exit code reported by libpod into an ExitStatus
+fn exit_status(exit_code: i32) -> ExitStatus {
+    ExitStatus::from_raw(exit_code << 8)
+}
+
+#[async_trait]
+impl SandboxBackend for PodmanApi {
+    fn name(&self) -> &'static str {
+        "podman-api"
+    }
+
+    fn supports_idle(&self) -> bool {
+        true
+    }
+
+    async fn pull_image(&self, settings: &ContainerSettings) -> Result<(), Error> {
+        self.client.pull(&settings.image).await
+    }
+
+    async fn execute(
+        &self,
+        settings: &ContainerSettings,
+        command: &[String],
+    ) -> Result<SandboxOutput, Error> {
+        let id = self.client.create(Self::spec(settings, command)).await?;
+        println!("Created container {}", id);
+
+        let result = self.run_container(&id, settings).await;
+
+        // Always clean up, even if the run itself failed
+        if let Err(e) = self.client.remove(&id).await {
+            println!("Error removing container {}: {:?}", id, e);
+        }
+
+        result
+    }
+
+    async fn start_idle(&self, settings: &ContainerSettings) -> Result<String, Error> {
+        let command = IDLE_COMMAND.map(String::from);
+        let id = self.client.create(Self::spec(settings, &command)).await?;
+
+        if let Err(e) = self.client.start(&id).await {
+            self.client.remove(&id).await.ok();
+            return Err(e);
+        }
+
+        Ok(id)
+    }
+
+    async fn execute_in(
+        &self,
+        id: &str,
+        settings: &ContainerSettings,
+        command: &[String],
+    ) -> Result<SandboxOutput, Error> {
+        let exec_id = self.client.exec_create(id, command).await?;
+        let output = async {
+            let body = self.client.exec_start(&exec_id).await?;
+            read_output(body).await
+        };
+
+        // The container is thrown away after the job, which also takes care of
+        // stopping the exec session when it times out
+        let output = tokio::time::timeout(settings.time_limit(), output)
+            .await
+            .map_err(|_| Error::new(io::ErrorKind::TimedOut, "Process timed out"))??;
+
+        let exit_code = self.client.exec_exit_code(&exec_id).await?;
+        Ok(output.finish(exit_status(exit_code)))
+    }
+
+    async fn remove(&self, id: &str) -> Result<(), Error> {
+        self.client.remove(id).await
+    }
+
+    async fn reap(&self) -> Result<usize, Error> {
+        let ids = self.client.list(CONTAINER_LABEL).await?;
+
+        for id in &ids {
+            self.client.remove(id).await?;
+        }
+
+        Ok(ids.len())
+    }
+}
@@ -0,0 +1,401 @@
+use serde_json::Value;
+
+/// How we made a snippet into a program rustc will run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrapping {
+    /// The code has a main of its own
+    None,
+    /// The code is put in a main
+    Main,
+    /// The code ends in an expression, whose value is printed with {:?}
+    Eval,
+}
+
+/// Code as the user wrote it, made into a whole program when it's a snippet
+/// without a main, eg. `vec![1, 2, 3].iter().sum::<i32>()`
+#[derive(Debug, Clone)]
+pub struct Program {
+    /// What actually gets compiled
+    pub source: String,
+    pub wrapping: Wrapping,
+    /// Lines at the start of the user's code that stay outside of main, as
+    /// crate attributes have to
+    hoisted: usize,
+    /// Lines we put between the hoisted lines and the rest of the code
+    added: usize,
+    /// Lines in the user's code
+    lines: usize,
+}
+
+/// Macros whose value, if any, isn't worth printing as it's either `()` or
+/// already printed by the macro itself
+const UNPRINTED_MACROS: [&str; 8] = [
+    "print!",
+    "println!",
+    "eprint!",
+    "eprintln!",
+    "dbg!",
+    "assert!",
+    "assert_eq!",
+    "assert_ne!",
+];
+
+/// `code` with its comments and the contents of its string literals blanked
+/// out, so what's in them isn't mistaken for code. Lines stay where they are
+fn code_only(code: &str) -> String {
+    let mut blanked = String::with_capacity(code.len());
+    let mut chars = code.chars().peekable();
+    let blank = |c: char| if c == '\n' { '\n' } else { ' ' };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                blanked.push(' ');
+                while let Some(&next) = chars.peek() {
+                    if next == '\n' {
+                        break;
+                    }
+                    blanked.push(' ');
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                blanked.push(' ');
+                let mut previous = ' ';
+                for next in chars.by_ref() {
+                    blanked.push(blank(next));
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+            }
+            '"' => {
+                blanked.push('"');
+                while let Some(next) = chars.next() {
+                    if next == '"' {
+                        blanked.push('"');
+                        break;
+                    }
+                    blanked.push(blank(next));
+                    if next == '\\' {
+                        if let Some(escaped) = chars.next() {
+                            blanked.push(blank(escaped));
+                        }
+                    }
+                }
+            }
+            // A quote in a char literal doesn't start a string
+            '\'' => {
+                blanked.push('\'');
+                let mut lookahead = chars.clone();
+                let is_char = match lookahead.next() {
+                    Some('\\') => true,
+                    Some(_) => lookahead.next() == Some('\''),
+                    None => false,
+                };
+                if is_char {
+                    while let Some(next) = chars.next() {
+                        blanked.push(blank(next));
+                        if next == '\'' {
+                            break;
+                        }
+                        // So '\'' doesn't end at the escaped quote
+                        if next == '\\' {
+                            if let Some(escaped) = chars.next() {
+                                blanked.push(blank(escaped));
+                            }
+                        }
+                    }
+                }
+            }
+            c => blanked.push(c),
+        }
+    }
+
+    blanked
+}
+
+/// Whether `code` has a main function somewhere, outside of comments and
+/// strings
+pub fn has_main(code: &str) -> bool {
+    let code = code_only(code);
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+
+    code.match_indices("fn main").any(|(start, found)| {
+        let before = code[..start].chars().next_back();
+        let after = code[start + found.len()..].trim_start();
+        !before.is_some_and(is_ident) && (after.starts_with('(') || after.starts_with('<'))
+    })
+}
+
+/// Lines that have to stay at the top of the crate, or may as well
+fn is_hoistable(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty()
+        || line.starts_with("#![")
+        || line.starts_with("extern crate")
+        || line.starts_with("//")
+}
+
+/// Whether the code ends in an expression worth printing the value of,
+/// rather than a statement or an item
+///
+/// Block-like expressions such as `match` or `if` end in a `}` and are taken
+/// for statements, their value isn't printed. Neither is that of a trailing
+/// `println!` and the like, which is `()`
+fn ends_in_expression(code: &str) -> bool {
+    let code = code_only(code);
+    let code = code.trim_end();
+    if code.is_empty() || code.ends_with(';') || code.ends_with('}') {
+        return false;
+    }
+
+    // With strings blanked out, the last statement is whatever comes after
+    // the last thing that ends a statement or opens a block
+    let last_statement = match code.rfind([';', '{', '}']) {
+        Some(end) => &code[end + 1..],
+        None => code,
+    };
+    let last_statement = last_statement.trim_start();
+    !UNPRINTED_MACROS
+        .iter()
+        .any(|name| last_statement.starts_with(name))
+}
+
+impl Program {
+    /// Runs `code` the way it is
+    pub fn unchanged(code: &str) -> Self {
+        Program {
+            source: code.to_string(),
+            wrapping: Wrapping::None,
+            hoisted: 0,
+            added: 0,
+            lines: code.lines().count(),
+        }
+    }
+
+    /// Puts `code` in a main unless it has one, printing its value when it
+    /// ends in an expression
+    pub fn new(code: &str) -> Self {
+        if has_main(code) {
+            return Self::unchanged(code);
+        }
+
+        let lines: Vec<&str> = code.lines().collect();
+        let hoisted = lines.iter().take_while(|line| is_hoistable(line)).count();
+        let (top, body) = lines.split_at(hoisted);
+        let body = body.join("\n");
+
+        let (wrapping, header, footer) = if ends_in_expression(&body) {
+            (
+                Wrapping::Eval,
+                "fn main() {\n    println!(\"{:?}\", {",
+                "    });\n}",
+            )
+        } else {
+            (Wrapping::Main, "fn main() {", "}")
+        };
+
+        let mut source = String::new();
+        for line in top {
+            source.push_str(line);
+            source.push('\n');
+        }
+        source.push_str(header);
+        source.push('\n');
+        source.push_str(&body);
+        source.push('\n');
+        source.push_str(footer);
+        source.push('\n');
+
+        Program {
+            source,
+            wrapping,
+            hoisted,
+            added: header.lines().count(),
+            lines: lines.len(),
+        }
+    }
+
+    /// The line of the user's code that `line` of the program came from
+    ///
+    /// Lines we added are put on the nearest line of the user's code, so
+    /// whatever points at them still points somewhere sensible
+    pub fn original_line(&self, line: usize) -> usize {
+        let line = if line <= self.hoisted {
+            line
+        } else if line <= self.hoisted + self.added {
+            self.hoisted + 1
+        } else {
+            line - self.added
+        };
+        line.clamp(1, self.lines.max(1))
+    }
+
+    /// Points the line numbers in the compiler's and the program's output
+    /// back at the user's code, both in JSON diagnostics and in locations like
+    /// `main.rs:5:9` of panics and Miri's reports
+    pub fn map_output(&self, output: &str) -> String {
+        if self.wrapping == Wrapping::None {
+            return output.to_string();
+        }
+
+        let mut mapped = String::new();
+        for line in output.lines() {
+            match serde_json::from_str::<Value>(line) {
+                Ok(mut diagnostic) if diagnostic.is_object() => {
+                    self.map_diagnostic(&mut diagnostic);
+                    mapped.push_str(&diagnostic.to_string());
+                }
+                _ => mapped.push_str(&self.map_locations(line)),
+            }
+            mapped.push('\n');
+        }
+        mapped
+    }
+
+    /// Maps the lines of the spans of a diagnostic and of its children
+    fn map_diagnostic(&self, diagnostic: &mut Value) {
+        if let Some(spans) = diagnostic["spans"].as_array_mut() {
+            for span in spans {
+                for key in ["line_start", "line_end"] {
+                    if let Some(line) = span[key].as_u64() {
+                        span[key] = Value::from(self.original_line(line as usize));
+                    }
+                }
+            }
+        }
+        if let Some(children) = diagnostic["children"].as_array_mut() {
+            for child in children {
+                self.map_diagnostic(child);
+            }
+        }
+    }
+
+    /// Maps the line of every `main.rs:LINE` in `text`
+    fn map_locations(&self, text: &str) -> String {
+        const FILE: &str = "main.rs:";
+
+        let mut mapped = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find(FILE) {
+            let (before, after) = rest.split_at(start + FILE.len());
+            mapped.push_str(before);
+
+            let digits = after
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(after.len());
+            match after[..digits].parse::<usize>() {
+                Ok(line) => mapped.push_str(&self.original_line(line).to_string()),
+                Err(_) => mapped.push_str(&after[..digits]),
+            }
+            rest = &after[digits..];
+        }
+        mapped.push_str(rest);
+
+        mapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_main_outside_of_comments_and_strings() {
+        assert!(has_main("fn main() {}"));
+        assert!(has_main("pub fn main<T>() {}"));
+        assert!(!has_main("// fn main() {}\nlet x = 1;"));
+        assert!(!has_main("/* fn main() {}\n*/ let x = 1;"));
+        assert!(!has_main("let s = \"fn main() {}\";"));
+        assert!(!has_main("let s = \"\\\" fn main() {}\";"));
+        assert!(!has_main("fn main_loop() {}"));
+        assert!(!has_main("fn not_main() {}"));
+    }
+
+    #[test]
+    fn tells_lifetimes_from_char_literals() {
+        assert!(has_main(
+            "fn first<'a>(s: &'a str) -> &'a str { s }\nfn main() {}"
+        ));
+        assert!(has_main("const Q: char = '\"';\nfn main() {}"));
+        assert!(has_main("const Q: char = '\\'';\nfn main() {}"));
+        assert!(!has_main("const Q: char = '\\'';\n// fn main() {}"));
+        assert_eq!(code_only("'a'"), "'  ");
+        assert_eq!(code_only("'\\''"), "'   ");
+        assert_eq!(code_only("&'a str"), "&'a str");
+    }
+
+    #[test]
+    fn keeps_lines_where_they_are() {
+        let code = "let s = \"one\ntwo\";\n/* three\nfour */ x";
+        let blanked = code_only(code);
+        assert_eq!(blanked.len(), code.len());
+        assert_eq!(blanked.lines().count(), code.lines().count());
+    }
+
+    #[test]
+    fn prints_only_expressions_worth_printing() {
+        assert!(ends_in_expression("1 + 1"));
+        assert!(ends_in_expression("let x = 1;\nx * 2"));
+        assert!(ends_in_expression(
+            "let v = vec![1];\nv.len() // the length"
+        ));
+        assert!(!ends_in_expression("let x = 1;"));
+        assert!(!ends_in_expression("if true { 1 } else { 2 }"));
+        assert!(!ends_in_expression("println!(\"hi\")"));
+        assert!(!ends_in_expression(
+            "let x = 1;\nprintln!(\"{}; {}\", x, x)"
+        ));
+        assert!(!ends_in_expression("assert_eq!(1, 1)"));
+        assert!(!ends_in_expression(""));
+    }
+
+    #[test]
+    fn wraps_snippets_in_main() {
+        assert_eq!(Program::new("fn main() {}").wrapping, Wrapping::None);
+        assert_eq!(Program::new("let x = 1;").wrapping, Wrapping::Main);
+        assert_eq!(Program::new("let x = 1;\nx").wrapping, Wrapping::Eval);
+        assert_eq!(Program::new("println!(\"hi\")").wrapping, Wrapping::Main);
+        assert_eq!(
+            Program::new("1 + 2").source,
+            "fn main() {\n    println!(\"{:?}\", {\n1 + 2\n    });\n}\n"
+        );
+    }
+
+    #[test]
+    fn maps_lines_back_past_hoisted_attributes() {
+        let program = Program::new("#![allow(unused)]\nlet x = 1;\nx");
+        assert_eq!(program.wrapping, Wrapping::Eval);
+        assert!(program.source.starts_with("#![allow(unused)]\nfn main() {"));
+
+        // The attribute stays put, our two lines of main point at the first
+        // line of the user's code, which then follows two lines later
+        assert_eq!(program.original_line(1), 1);
+        assert_eq!(program.original_line(2), 2);
+        assert_eq!(program.original_line(3), 2);
+        assert_eq!(program.original_line(4), 2);
+        assert_eq!(program.original_line(5), 3);
+        assert_eq!(program.original_line(7), 3);
+
+        assert_eq!(
+            program.map_output("panicked at main.rs:5:1:\nsrc/main.rs:4:9"),
+            "panicked at main.rs:3:1:\nsrc/main.rs:2:9\n"
+        );
+        let mapped = program.map_output(
+            r#"{"message":"m","spans":[{"line_start":4,"line_end":5}],"children":[{"spans":[{"line_start":5,"line_end":5}]}]}"#,
+        );
+        let diagnostic: Value = serde_json::from_str(mapped.trim()).unwrap();
+        assert_eq!(diagnostic["spans"][0]["line_start"], 2);
+        assert_eq!(diagnostic["spans"][0]["line_end"], 3);
+        assert_eq!(diagnostic["children"][0]["spans"][0]["line_start"], 3);
+    }
+
+    #[test]
+    fn leaves_programs_with_a_main_alone() {
+        let program = Program::new("fn main() {\n    panic!()\n}");
+        assert_eq!(program.original_line(2), 2);
+        assert_eq!(program.map_output("main.rs:2:5"), "main.rs:2:5");
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+
+use crate::configuration;
+use crate::model::configurable::ConfigurableValue;
+use crate::model::container::ContainerSettings;
+
+/// Rust release channels code can be run on, each with its own runner image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, poise::ChoiceParameter)]
+pub enum Toolchain {
+    #[default]
+    #[name = "stable"]
+    Stable,
+    #[name = "beta"]
+    Beta,
+    #[name = "nightly"]
+    Nightly,
+}
+
+impl Toolchain {
+    pub const ALL: [Toolchain; 3] = [Toolchain::Stable, Toolchain::Beta, Toolchain::Nightly];
+
+    /// Runner image for this channel, None if it isn't configured
+    pub fn image(&self) -> Option<String> {
+        let image = match self {
+            Self::Stable => configuration::CONTAINER_IMAGE.value(),
+            Self::Beta => configuration::CONTAINER_IMAGE_BETA.value(),
+            Self::Nightly => configuration::CONTAINER_IMAGE_NIGHTLY.value(),
+        };
+
+        if image.is_empty() {
+            None
+        } else {
+            Some(image)
+        }
+    }
+
+    /// Every channel that has a runner image configured
+    pub fn available() -> Vec<Toolchain> {
+        Self::ALL
+            .into_iter()
+            .filter(|toolchain| toolchain.image().is_some())
+            .collect()
+    }
+
+    /// `settings` with the runner image swapped out for this channel's
+    pub fn settings(&self, settings: &ContainerSettings) -> Option<ContainerSettings> {
+        let image = self.image()?;
+        Some(ContainerSettings {
+            image,
+            ..settings.clone()
+        })
+    }
+}
+
+impl fmt::Display for Toolchain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stable => write!(f, "stable"),
+            Self::Beta => write!(f, "beta"),
+            Self::Nightly => write!(f, "nightly"),
+        }
+    }
+}
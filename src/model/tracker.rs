@@ -0,0 +1,102 @@
+use serenity::model::id::{MessageId, UserId};
+
+use crate::model::options::RunOptions;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// How many runs we keep track of, the oldest ones are forgotten first
+const MAX_TRACKED: usize = 1000;
+
+/// Where in its source message the code of a run came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodeSource {
+    /// A prefix command, the message starts with `invocation` (eg. "!run"),
+    /// followed by flags and a code block
+    Prefix { invocation: String },
+    /// The code block with this index, picked from the context menu
+    Block { index: usize },
+}
+
+/// A run of the code in a message, so it can be redone when the message changes
+#[derive(Debug, Clone)]
+pub struct TrackedRun {
+    /// Who started the run, and is charged for re-running it
+    pub user: UserId,
+    /// The message we replied with, in the same channel as the source
+    pub result: MessageId,
+    pub source: CodeSource,
+    /// What was run last, edits that leave these be don't need another run
+    pub code: String,
+    pub options: RunOptions,
+}
+
+#[derive(Default)]
+struct TrackerState {
+    runs: HashMap<MessageId, TrackedRun>,
+    /// Source messages in the order they were tracked
+    order: VecDeque<MessageId>,
+    /// The latest edit of each source message, edits are numbered so a re-run
+    /// can tell a newer one came in while it ran
+    edits: HashMap<MessageId, u64>,
+    next_edit: u64,
+}
+
+/// Remembers which result message belongs to which source message
+#[derive(Default)]
+pub struct RunTracker {
+    state: Mutex<TrackerState>,
+}
+
+impl RunTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn track(&self, source: MessageId, run: TrackedRun) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.runs.insert(source, run).is_none() {
+            state.order.push_back(source);
+        }
+        while state.order.len() > MAX_TRACKED {
+            if let Some(oldest) = state.order.pop_front() {
+                state.runs.remove(&oldest);
+                state.edits.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn get(&self, source: MessageId) -> Option<TrackedRun> {
+        self.state.lock().unwrap().runs.get(&source).cloned()
+    }
+
+    /// Notes that `source` was edited, returning the number of this edit
+    pub fn begin_edit(&self, source: MessageId) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.next_edit += 1;
+        let edit = state.next_edit;
+        state.edits.insert(source, edit);
+        edit
+    }
+
+    /// Whether `edit` is still the latest edit of `source`
+    pub fn is_latest_edit(&self, source: MessageId, edit: u64) -> bool {
+        self.state.lock().unwrap().edits.get(&source) == Some(&edit)
+    }
+
+    /// Tracks what `edit` ran, unless a newer edit came in meanwhile
+    pub fn track_edit(&self, source: MessageId, edit: u64, run: TrackedRun) {
+        if self.is_latest_edit(source, edit) {
+            self.track(source, run);
+        }
+    }
+
+    /// Stops tracking the run of `source`, returning it if there was one
+    pub fn forget(&self, source: MessageId) -> Option<TrackedRun> {
+        let mut state = self.state.lock().unwrap();
+        state.edits.remove(&source);
+        let run = state.runs.remove(&source)?;
+        state.order.retain(|id| *id != source);
+        Some(run)
+    }
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+
+use crate::model::container::ContainerSettings;
+use crate::model::explain::ErrorCode;
+use crate::model::options::{Edition, Emit, Mode, Profile, RunOptions};
+use crate::model::snippet::has_main;
+
+/// Script that builds and runs programs inside the sandbox
+const SCRIPT: &str = include_str!("../../runner/trampoline.sh");
+
+/// Prefix of the lines the trampoline uses to report back to us
+pub const REPORT_PREFIX: &str = "ferris-bot: ";
+
+/// What the trampoline should do with the code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Task {
+    /// Build the code and do whatever the mode says with it, as for /run
+    Run(Mode),
+    /// Lint the code with clippy
+    Clippy,
+    /// Format the code with rustfmt
+    Fmt,
+    /// Compile the code and output what the compiler made of it
+    Emit { emit: Emit, profile: Profile },
+    /// Output the code with its macros expanded
+    Expand,
+    /// Output rustc's explanation of an error code, the code itself is ignored
+    Explain(ErrorCode),
+}
+
+impl Task {
+    /// Whether this needs tools or flags that only come with nightly
+    pub fn needs_nightly(&self) -> bool {
+        match self {
+            Self::Run(mode) => mode.needs_nightly(),
+            Self::Emit { emit, .. } => emit.needs_nightly(),
+            Self::Expand => true,
+            Self::Clippy | Self::Fmt | Self::Explain(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for Task {
+    /// Name of the task as the trampoline knows it
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Run(mode) => write!(f, "{}", mode),
+            Self::Clippy => write!(f, "clippy"),
+            Self::Fmt => write!(f, "fmt"),
+            Self::Emit { .. } => write!(f, "emit"),
+            Self::Expand => write!(f, "expand"),
+            Self::Explain(_) => write!(f, "explain"),
+        }
+    }
+}
+
+/// Everything the trampoline needs to know about a run
+pub struct Payload {
+    /// Source of the program to run
+    pub code: String,
+    /// Edition to compile the program with
+    pub edition: Edition,
+    /// What to do with the program
+    pub task: Task,
+    /// How long compiling may take, in ms
+    pub compile_timeout: u64,
+    /// How long the program may run for, in ms
+    pub run_timeout: u64,
+}
+
+impl Payload {
+    /// Creates a payload for `code` with the budgets from `settings`
+    pub fn new(code: &str, settings: &ContainerSettings, options: &RunOptions, task: Task) -> Self {
+        Payload {
+            code: code.to_string(),
+            edition: options.edition,
+            task,
+            compile_timeout: settings.max_compile_time,
+            run_timeout: settings.max_runtime,
+        }
+    }
+
+    /// The command that runs the trampoline inside the container
+    ///
+    /// Every option is a single key=value argument, and the program is base64
+    /// encoded so it survives being passed as an argument untouched
+    pub fn command(&self) -> Vec<String> {
+        let mut command = vec![
+            String::from("sh"),
+            String::from("-c"),
+            String::from(SCRIPT),
+            String::from("trampoline"),
+        ];
+
+        command.push(format!("compile-timeout={}", seconds(self.compile_timeout)));
+        command.push(format!("run-timeout={}", seconds(self.run_timeout)));
+        command.push(format!("edition={}", self.edition));
+        command.push(format!("mode={}", self.task));
+        if let Task::Emit { emit, profile } = self.task {
+            command.push(format!("emit={}", emit));
+            command.push(format!("profile={}", profile));
+
+            // Functions on their own are compiled as a library, like the
+            // playground does, rather than failing for lack of a main
+            let crate_type = if has_main(&self.code) { "bin" } else { "lib" };
+            command.push(format!("crate-type={}", crate_type));
+        }
+        if let Task::Explain(error_code) = self.task {
+            command.push(format!("error-code={}", error_code));
+        }
+        command.push(format!("code={}", base64::encode(&self.code)));
+
+        command
+    }
+}
+
+/// Formats ms as seconds the way coreutils' timeout expects them
+fn seconds(ms: u64) -> String {
+    format!("{}.{:03}", ms / 1000, ms % 1000)
+}